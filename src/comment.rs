@@ -0,0 +1,28 @@
+use crate::feature::owned;
+use crate::location::Span;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// A `#` comment line, attached to the node which follows it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comment<'a> {
+    /// The whole comment, including its leading `#`.
+    pub text: Cow<'a, str>,
+    #[serde(default)]
+    pub span: Span,
+}
+
+impl<'a> Comment<'a> {
+    /// The comment with its leading `#` and surrounding whitespace removed.
+    pub fn content(&self) -> &str {
+        self.text.trim_start_matches('#').trim()
+    }
+
+    /// Copies the text if it's borrowed from the input.
+    pub fn into_owned(self) -> Comment<'static> {
+        Comment {
+            text: owned(self.text),
+            span: self.span,
+        }
+    }
+}
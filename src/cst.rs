@@ -0,0 +1,350 @@
+//! A lossless syntax tree of a feature file, for tools which edit feature files.
+//!
+//! Every line of the file is kept exactly as written, along with its line
+//! ending, so writing the tree out with [`ToString`] reproduces the file byte
+//! for byte. Lines are grouped into nodes by the headers and steps they
+//! follow, so that a tool can find a node, change its lines, and write the
+//! file back out with the rest of it untouched.
+//!
+//! Blank lines, comments and tags belong to the node which follows them.
+//! Any at the end of the file belong to the [`NodeKind::Document`].
+//!
+//! Lines are classified by the same lexer the AST is parsed from, so a line
+//! is a step or a header in the tree exactly when it is one in the AST.
+
+use crate::error::{Diagnostics, ParseResult};
+use crate::feature::Feature;
+use crate::gherkin_tags::{GherkinLine, GherkinLines, GroupingKeyword, StepKeyword};
+use std::borrow::Cow;
+use std::fmt;
+
+/// What a line of a feature file holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Blank,
+    Comment,
+    /// The `# language:` header.
+    Language,
+    Tags,
+    /// The header of a node, such as `Scenario: Feed the goat`.
+    Header(NodeKind),
+    Step(StepKeyword),
+    TableRow,
+    /// The line opening or closing a doc string.
+    DocStringDelimiter,
+    DocStringContent,
+    /// Any other text, such as a description.
+    Other,
+}
+
+/// A line of a feature file.
+#[derive(Debug, Clone)]
+pub struct SyntaxLine<'a> {
+    pub kind: LineKind,
+    /// The line as written, without its line ending. Replacement text must not
+    /// contain line endings; add more lines to the node instead.
+    pub text: Cow<'a, str>,
+    /// `"\n"`, `"\r\n"`, or nothing for a last line without a line ending.
+    pub line_ending: &'a str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// The whole file.
+    Document,
+    Feature,
+    Rule,
+    Background,
+    Scenario,
+    ScenarioOutline,
+    Examples,
+    /// A step, along with its doc string or data table.
+    Step,
+}
+
+impl NodeKind {
+    /// Whether a node of this kind can hold nodes of kind `child`.
+    fn holds(self, child: NodeKind) -> bool {
+        use NodeKind::*;
+        match self {
+            Document => child == Feature,
+            Feature => matches!(child, Rule | Background | Scenario | ScenarioOutline),
+            Rule => matches!(child, Background | Scenario | ScenarioOutline),
+            Background => child == Step,
+            Scenario | ScenarioOutline => matches!(child, Step | Examples),
+            Examples | Step => false,
+        }
+    }
+}
+
+impl From<GroupingKeyword> for NodeKind {
+    fn from(keyword: GroupingKeyword) -> Self {
+        match keyword {
+            GroupingKeyword::ScenarioOutline => NodeKind::ScenarioOutline,
+            GroupingKeyword::Scenario => NodeKind::Scenario,
+            GroupingKeyword::Background => NodeKind::Background,
+            GroupingKeyword::Examples => NodeKind::Examples,
+            GroupingKeyword::Feature => NodeKind::Feature,
+            GroupingKeyword::Rule => NodeKind::Rule,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement<'a> {
+    Node(SyntaxNode<'a>),
+    Line(SyntaxLine<'a>),
+}
+
+#[derive(Debug, Clone)]
+pub struct SyntaxNode<'a> {
+    pub kind: NodeKind,
+    /// The lines and nodes within this node, in the order they were written.
+    pub children: Vec<SyntaxElement<'a>>,
+}
+
+impl<'a> SyntaxNode<'a> {
+    fn new(kind: NodeKind) -> Self {
+        SyntaxNode {
+            kind,
+            children: vec![],
+        }
+    }
+
+    /// The nodes directly within this node.
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode<'a>> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Line(_) => None,
+        })
+    }
+
+    pub fn nodes_mut(&mut self) -> impl Iterator<Item = &mut SyntaxNode<'a>> {
+        self.children.iter_mut().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Line(_) => None,
+        })
+    }
+
+    /// The header line of this node, or the line of the step for a step.
+    pub fn header(&self) -> Option<&SyntaxLine<'a>> {
+        self.children.iter().find_map(|child| match child {
+            SyntaxElement::Line(line) if is_header(line.kind) => Some(line),
+            _ => None,
+        })
+    }
+
+    pub fn header_mut(&mut self) -> Option<&mut SyntaxLine<'a>> {
+        self.children.iter_mut().find_map(|child| match child {
+            SyntaxElement::Line(line) if is_header(line.kind) => Some(line),
+            _ => None,
+        })
+    }
+
+    /// Every line within this node, including those within the nodes it holds, in order.
+    pub fn lines(&self) -> Vec<&SyntaxLine<'a>> {
+        let mut lines = vec![];
+        self.collect_lines(&mut lines);
+        lines
+    }
+
+    fn collect_lines<'n>(&'n self, lines: &mut Vec<&'n SyntaxLine<'a>>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.collect_lines(lines),
+                SyntaxElement::Line(line) => lines.push(line),
+            }
+        }
+    }
+}
+
+fn is_header(kind: LineKind) -> bool {
+    matches!(kind, LineKind::Header(_) | LineKind::Step(_))
+}
+
+impl<'a> fmt::Display for SyntaxNode<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in self.lines() {
+            write!(f, "{}{}", line.text, line.line_ending)?;
+        }
+        Ok(())
+    }
+}
+
+/// A feature file, as a tree of its lines.
+#[derive(Debug, Clone)]
+pub struct SyntaxTree<'a> {
+    /// The [`NodeKind::Document`] node holding the whole file.
+    pub root: SyntaxNode<'a>,
+}
+
+impl<'a> SyntaxTree<'a> {
+    /// Reads every line of `input`. This only fails if `input` names a
+    /// language which isn't supported, since any line can be kept as [`LineKind::Other`].
+    pub fn parse(input: &'a str) -> ParseResult<Self> {
+        let lines = split_lines(input);
+        let texts: Vec<&str> = lines.iter().map(|(text, _)| *text).collect();
+        let kinds = line_kinds(texts)?;
+        let mut builder = TreeBuilder {
+            stack: vec![SyntaxNode::new(NodeKind::Document)],
+            pending: vec![],
+        };
+        for ((text, line_ending), kind) in lines.into_iter().zip(kinds) {
+            builder.push(SyntaxLine {
+                kind,
+                text: Cow::Borrowed(text),
+                line_ending,
+            });
+        }
+        Ok(SyntaxTree {
+            root: builder.finish(),
+        })
+    }
+
+    /// Parses the AST of the feature file as the tree now stands, stopping at the first error.
+    pub fn to_feature(&self) -> ParseResult<Feature<'_>> {
+        let lines = self.root.lines().into_iter().map(|line| line.text.as_ref());
+        Feature::parse_lines(
+            GherkinLines::from_lines(lines.collect())?,
+            &mut Diagnostics::strict(),
+        )
+    }
+}
+
+impl<'a> fmt::Display for SyntaxTree<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.root.fmt(f)
+    }
+}
+
+/// Splits `input` into lines, each paired with its line ending.
+fn split_lines(input: &str) -> Vec<(&str, &str)> {
+    let mut lines = vec![];
+    let mut rest = input;
+    while !rest.is_empty() {
+        let (line, after) = match rest.find('\n') {
+            Some(index) => rest.split_at(index + 1),
+            None => (rest, ""),
+        };
+        let text = line.trim_end_matches('\n').trim_end_matches('\r');
+        lines.push((text, &line[text.len()..]));
+        rest = after;
+    }
+    lines
+}
+
+/// Works out what each line holds from the tokens the AST parser reads
+/// them as, so that the tree and the AST never disagree about a line.
+fn line_kinds(texts: Vec<&str>) -> ParseResult<Vec<LineKind>> {
+    let mut kinds: Vec<LineKind> = texts
+        .iter()
+        .map(|text| match text.trim() {
+            "" => LineKind::Blank,
+            _ => LineKind::Other,
+        })
+        .collect();
+    let line_count = kinds.len();
+    let mut tokens = GherkinLines::from_lines(texts)?;
+    // Line numbers count from 1.
+    let mut set = |line: usize, kind: LineKind| kinds[line - 1] = kind;
+    for (token, span) in &mut tokens {
+        let line = span.start.line;
+        match token {
+            GherkinLine::Tags(tags) => {
+                for tag in tags {
+                    set(tag.span.start.line, LineKind::Tags);
+                }
+            }
+            GherkinLine::BeginGroup(keyword, _, _, tags) => {
+                for tag in tags {
+                    set(tag.span.start.line, LineKind::Tags);
+                }
+                set(line, LineKind::Header(keyword.into()));
+            }
+            GherkinLine::StepLine(keyword, _, _) => set(line, LineKind::Step(keyword)),
+            GherkinLine::ExampleEntry(_) => set(line, LineKind::TableRow),
+            GherkinLine::FreeText(..) => set(line, LineKind::Other),
+            GherkinLine::DocString(_) => {
+                set(line, LineKind::DocStringDelimiter);
+                for content_line in line + 1..span.end.line {
+                    set(content_line, LineKind::DocStringContent);
+                }
+                set(span.end.line, LineKind::DocStringDelimiter);
+            }
+            // The rest of the file is read as the contents of the doc string.
+            GherkinLine::UnterminatedDocString(_) => {
+                set(line, LineKind::DocStringDelimiter);
+                for content_line in line + 1..=line_count {
+                    set(content_line, LineKind::DocStringContent);
+                }
+            }
+        }
+    }
+    for comment in &tokens.comments {
+        set(comment.span.start.line, LineKind::Comment);
+    }
+    if let Some(line) = tokens.language_line {
+        set(line, LineKind::Language);
+    }
+    Ok(kinds)
+}
+
+/// Builds the tree one line at a time, keeping the nodes still being read on a stack.
+struct TreeBuilder<'a> {
+    stack: Vec<SyntaxNode<'a>>,
+    /// Blank lines, comments and tags, waiting to see which node they belong to.
+    pending: Vec<SyntaxLine<'a>>,
+}
+
+impl<'a> TreeBuilder<'a> {
+    fn push(&mut self, line: SyntaxLine<'a>) {
+        match line.kind {
+            LineKind::Blank | LineKind::Comment | LineKind::Language | LineKind::Tags => {
+                self.pending.push(line)
+            }
+            LineKind::Header(kind) => self.open(kind, line),
+            LineKind::Step(_) => self.open(NodeKind::Step, line),
+            _ => self.append(line),
+        }
+    }
+
+    /// Starts a node of `kind` within the innermost node which can hold it,
+    /// or keeps `line` as it is if no node can.
+    fn open(&mut self, kind: NodeKind, line: SyntaxLine<'a>) {
+        let parent = match self.stack.iter().rposition(|node| node.kind.holds(kind)) {
+            Some(parent) => parent,
+            None => return self.append(line),
+        };
+        while self.stack.len() > parent + 1 {
+            self.close();
+        }
+        let mut node = SyntaxNode::new(kind);
+        node.children
+            .extend(self.pending.drain(..).map(SyntaxElement::Line));
+        node.children.push(SyntaxElement::Line(line));
+        self.stack.push(node);
+    }
+
+    fn append(&mut self, line: SyntaxLine<'a>) {
+        let node = self.stack.last_mut().expect("The document is never closed");
+        node.children
+            .extend(self.pending.drain(..).map(SyntaxElement::Line));
+        node.children.push(SyntaxElement::Line(line));
+    }
+
+    fn close(&mut self) {
+        let node = self.stack.pop().expect("The document is never closed");
+        let parent = self.stack.last_mut().expect("The document is never closed");
+        parent.children.push(SyntaxElement::Node(node));
+    }
+
+    fn finish(mut self) -> SyntaxNode<'a> {
+        while self.stack.len() > 1 {
+            self.close();
+        }
+        let mut root = self.stack.pop().expect("The document is never closed");
+        root.children
+            .extend(self.pending.drain(..).map(SyntaxElement::Line));
+        root
+    }
+}
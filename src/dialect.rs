@@ -0,0 +1,135 @@
+//! Keyword dictionaries for each language gherkin can be written in.
+//!
+//! `gherkin-languages.json` is copied unmodified from the official
+//! cucumber/gherkin repository, and can be refreshed by copying it again.
+
+use crate::gherkin_tags::{GroupingKeyword, StepKeyword};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// The language assumed when a feature file has no `# language:` header.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dialect {
+    /// The name of the language in that language, such as `Deutsch`.
+    pub native: String,
+    feature: Vec<String>,
+    rule: Vec<String>,
+    background: Vec<String>,
+    scenario: Vec<String>,
+    scenario_outline: Vec<String>,
+    examples: Vec<String>,
+    given: Vec<String>,
+    when: Vec<String>,
+    then: Vec<String>,
+    and: Vec<String>,
+    but: Vec<String>,
+    #[serde(skip)]
+    grouping_keywords: HashMap<String, GroupingKeyword>,
+    /// Step keywords, longest first so that e.g. `Et qu'` wins over `Et `.
+    #[serde(skip)]
+    step_keywords: Vec<(String, StepKeyword)>,
+}
+
+fn dialects() -> &'static HashMap<String, Dialect> {
+    static DIALECTS: OnceLock<HashMap<String, Dialect>> = OnceLock::new();
+    DIALECTS.get_or_init(|| {
+        let mut dialects: HashMap<String, Dialect> =
+            serde_json::from_str(include_str!("gherkin-languages.json"))
+                .expect("The embedded gherkin-languages.json is malformed");
+        for dialect in dialects.values_mut() {
+            dialect.build_lookup_tables();
+        }
+        dialects
+    })
+}
+
+impl Dialect {
+    pub fn for_language(language: &str) -> Option<&'static Dialect> {
+        dialects().get(language)
+    }
+
+    /// Lists the supported languages, such as `de (Deutsch)`, for use in messages.
+    pub fn supported_languages() -> Vec<String> {
+        let mut supported = dialects()
+            .iter()
+            .map(|(code, dialect)| format!("{} ({})", code, dialect.native))
+            .collect::<Vec<_>>();
+        supported.sort();
+        supported
+    }
+
+    pub fn english() -> &'static Dialect {
+        Self::for_language(DEFAULT_LANGUAGE).expect("English is always supported")
+    }
+
+    fn build_lookup_tables(&mut self) {
+        use GroupingKeyword::*;
+        for (keywords, grouping_keyword) in [
+            (&self.feature, Feature),
+            (&self.rule, Rule),
+            (&self.background, Background),
+            (&self.scenario, Scenario),
+            (&self.scenario_outline, ScenarioOutline),
+            (&self.examples, Examples),
+        ] {
+            for keyword in keywords {
+                self.grouping_keywords
+                    .insert(keyword.clone(), grouping_keyword);
+            }
+        }
+
+        use StepKeyword::*;
+        for (keywords, step_keyword) in [
+            (&self.given, Given),
+            (&self.when, When),
+            (&self.then, Then),
+            (&self.and, And),
+            (&self.but, But),
+        ] {
+            for keyword in keywords {
+                // `*` is listed under every step keyword, but stands on its own.
+                let step_keyword = if keyword.trim() == "*" {
+                    Bullet
+                } else {
+                    step_keyword
+                };
+                if !self.step_keywords.iter().any(|(k, _)| k == keyword) {
+                    self.step_keywords.push((keyword.clone(), step_keyword));
+                }
+            }
+        }
+        self.step_keywords
+            .sort_by_key(|(keyword, _)| std::cmp::Reverse(keyword.len()));
+    }
+
+    /// Looks up a keyword which begins a group, such as `Feature` or `Szenario`.
+    pub fn grouping_keyword(&self, keyword: &str) -> Option<GroupingKeyword> {
+        self.grouping_keywords.get(keyword.trim()).copied()
+    }
+
+    /// Splits a line beginning with a step keyword into the keyword
+    /// (as written) and the remaining step text.
+    pub fn split_step<'a>(&self, line: &'a str) -> Option<(StepKeyword, &'a str, &'a str)> {
+        self.step_keywords
+            .iter()
+            .find(|(k, _)| line.starts_with(k.as_str()))
+            .map(|(k, step_keyword)| {
+                let (keyword, text) = line.split_at(k.len());
+                (*step_keyword, keyword, text.trim())
+            })
+    }
+}
+
+/// Reads the language from a `# language: xx` comment, if that is what `line` is.
+pub(crate) fn language_header(line: &str) -> Option<&str> {
+    let (key, language) = line.trim().strip_prefix('#')?.split_once(':')?;
+    if key.trim() == "language" {
+        Some(language.trim())
+    } else {
+        None
+    }
+}
@@ -0,0 +1,186 @@
+use crate::dialect::Dialect;
+use crate::location::Span;
+use std::fmt;
+
+/// Everything that can go wrong while parsing a feature file.
+///
+/// Each variant has a stable [`code`](ParseError::code), so that tools can
+/// map failures to their own diagnostics without matching on message text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input contained no keywords at all.
+    Empty,
+    /// The `# language:` header named a language with no keyword dictionary.
+    UnsupportedLanguage { language: String, span: Span },
+    /// A line appeared somewhere it isn't allowed.
+    UnexpectedLine {
+        span: Span,
+        expected: &'static str,
+        found: String,
+    },
+    /// The input ended partway through the construct at `span`.
+    UnexpectedEof { span: Span, expected: &'static str },
+    /// A `<placeholder>` in a step was never closed with `>`.
+    UnterminatedPlaceholder {
+        span: Span,
+        step: String,
+        expression: String,
+    },
+    /// A doc string was opened, but its closing delimiter was never found.
+    UnterminatedDocString { span: Span, delimiter: String },
+    /// A table row which doesn't both begin and end with `|`.
+    MalformedTableRow { span: Span, row: String },
+    /// A table row with a different number of cells than the rows before it.
+    InconsistentTableWidth {
+        span: Span,
+        expected: usize,
+        found: usize,
+    },
+    /// A second `Background:` for the same feature or rule.
+    DuplicateBackground { span: Span, existing: Span },
+    /// A scenario outline with no `Examples:` blocks.
+    MissingExamples { span: Span, outline: String },
+}
+
+impl ParseError {
+    /// A short identifier for this kind of error, which won't change between releases.
+    pub fn code(&self) -> &'static str {
+        use ParseError::*;
+        match self {
+            Empty => "empty-feature",
+            UnsupportedLanguage { .. } => "unsupported-language",
+            UnexpectedLine { .. } => "unexpected-line",
+            UnexpectedEof { .. } => "unexpected-eof",
+            UnterminatedPlaceholder { .. } => "unterminated-placeholder",
+            UnterminatedDocString { .. } => "unterminated-doc-string",
+            MalformedTableRow { .. } => "malformed-table-row",
+            InconsistentTableWidth { .. } => "inconsistent-table-width",
+            DuplicateBackground { .. } => "duplicate-background",
+            MissingExamples { .. } => "missing-examples",
+        }
+    }
+
+    /// Where in the input the error was found, if it relates to a particular place.
+    pub fn span(&self) -> Option<Span> {
+        use ParseError::*;
+        match self {
+            Empty => None,
+            UnsupportedLanguage { span, .. }
+            | UnexpectedLine { span, .. }
+            | UnexpectedEof { span, .. }
+            | UnterminatedPlaceholder { span, .. }
+            | UnterminatedDocString { span, .. }
+            | MalformedTableRow { span, .. }
+            | InconsistentTableWidth { span, .. }
+            | DuplicateBackground { span, .. }
+            | MissingExamples { span, .. } => Some(*span),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ParseError::*;
+        match self {
+            Empty => write!(f, "Feature file was empty."),
+            UnsupportedLanguage { language, span } => write!(
+                f,
+                "Unsupported language `{}` at {} (expected one of {})",
+                language,
+                span,
+                Dialect::supported_languages().join(", ")
+            ),
+            UnexpectedLine {
+                span,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Unexpected {} at {} (expected {})",
+                found, span, expected
+            ),
+            UnexpectedEof { span, expected } => write!(
+                f,
+                "Unexpected EOF after the content at {} (expected {})",
+                span, expected
+            ),
+            UnterminatedPlaceholder {
+                span,
+                step,
+                expression,
+            } => write!(
+                f,
+                "The step `{}` at {} ends with an unterminated variable expression `{}`",
+                step, span, expression
+            ),
+            UnterminatedDocString { span, delimiter } => write!(
+                f,
+                "The doc string opened with `{}` at {} was never closed",
+                delimiter, span
+            ),
+            MalformedTableRow { span, row } => write!(
+                f,
+                "The table row at {} seems to be malformed, containing less than two pipes: `{}`",
+                span, row
+            ),
+            InconsistentTableWidth {
+                span,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Encountered row of length {} in table at {}, \
+                which was not consistent with the {} cells of the first row",
+                found, span, expected
+            ),
+            DuplicateBackground { span, existing } => write!(
+                f,
+                "Encountered a Background at {}, but another background \
+                was already declared at {}",
+                span, existing
+            ),
+            MissingExamples { span, outline } => write!(
+                f,
+                "The scenario outline `{}` at {} has no `Examples:` block",
+                outline, span
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub type ParseResult<T> = Result<T, ParseError>;
+
+/// Where parse errors are sent. When recovering, each error is recorded and
+/// parsing carries on; otherwise the first error ends the parse.
+pub(crate) struct Diagnostics {
+    recovering: bool,
+    pub(crate) errors: Vec<ParseError>,
+}
+
+impl Diagnostics {
+    pub(crate) fn strict() -> Self {
+        Diagnostics {
+            recovering: false,
+            errors: vec![],
+        }
+    }
+
+    pub(crate) fn recovering() -> Self {
+        Diagnostics {
+            recovering: true,
+            errors: vec![],
+        }
+    }
+
+    /// Records `error` if recovering, or returns it so that it can be propagated.
+    pub(crate) fn report(&mut self, error: ParseError) -> ParseResult<()> {
+        if self.recovering {
+            self.errors.push(error);
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+}
@@ -1,237 +1,96 @@
-use crate::feature::{ExampleBlock, ScenarioOutline};
+//! Rendering features in other formats, such as source code for a test framework.
+//!
+//! Each format is a type implementing [`Export`] for the AST nodes. A value of
+//! that type is passed to [`Export::export`], carrying the options for the format.
+//! Formats are gated behind cargo features of the same name, all on by default.
+//!
+//! A format can be written by implementing [`Visit`](crate::Visit) for a type
+//! which builds up the output as it goes, as the NUnit format is.
 
+use crate::feature::{FeatureItem, Scenario, ScenarioOutline};
+#[cfg(any(feature = "messages", feature = "gherkin"))]
+use crate::{feature::Rule, location::Location};
+
+#[cfg(feature = "gherkin")]
+mod gherkin;
+#[cfg(feature = "gherkin")]
+pub use gherkin::Gherkin;
+#[cfg(feature = "messages")]
+mod messages;
+#[cfg(feature = "messages")]
+pub use messages::{GherkinDocument, Messages};
+#[cfg(feature = "nunit")]
+mod nunit;
+#[cfg(feature = "nunit")]
+pub use nunit::NUnit;
+
+/// Renders a node in the format `T`.
+///
+/// Exporting a [`Feature`](crate::Feature) produces a complete document in that format, such
+/// as a C# source file. Exporting other nodes produces the fragment of that
+/// document for the node, which is only meaningful within its enclosing node.
 pub trait Export<T> {
     fn export(&self, export_format: T) -> String;
 }
 
-#[derive(PartialEq, Eq, Clone, Copy)]
-pub enum CSType {
-    Bool,
-    Int64,
-    Double,
-    String,
-}
-
-impl CSType {
-    fn lowest_common_type(self, other: CSType) -> CSType {
-        use CSType::*;
-        match (self, other) {
-            // Types remain the same unless contradicted
-            (x, y) if x == y => x,
-            // If a contradiction occurs, we default back to string
-            _ => String,
-        }
-    }
-    fn from(input: &str) -> CSType {
-        if input.parse::<i64>().is_ok() {
-            CSType::Int64
-        } else if input.parse::<f64>().is_ok() {
-            CSType::Double
-        } else if input.parse::<bool>().is_ok() {
-            CSType::Bool
-        } else {
-            CSType::String
-        }
-    }
-
-    fn to_str(self) -> &'static str {
+impl<'a, T> Export<T> for FeatureItem<'a>
+where
+    Scenario<'a>: Export<T>,
+    ScenarioOutline<'a>: Export<T>,
+{
+    fn export(&self, export_format: T) -> String {
         match self {
-            CSType::Bool => "bool",
-            CSType::Int64 => "long",
-            CSType::Double => "double",
-            CSType::String => "string",
-        }
-    }
-}
-pub struct NUnit;
-pub fn camel(input: &str) -> String {
-    let mut output = String::new();
-    let mut iterator = input.split(|c: char| !c.is_alphanumeric());
-    let first_word = if let Some(first_word) = iterator.next() {
-        first_word
-    } else {
-        return String::from("");
-    };
-    output += first_word;
-    for word in iterator {
-        let mut chars = word.chars();
-        if let Some(first_char) = chars.next() {
-            let first_upper = first_char.to_uppercase();
-            output.extend(first_upper);
-            output.extend(chars);
+            FeatureItem::Bare(x) => x.export(export_format),
+            FeatureItem::Outline(x) => x.export(export_format),
         }
     }
-    output
 }
 
-pub fn pascal(input: &str) -> String {
-    let mut output = String::new();
-    for word in input.split(|c: char| !c.is_alphanumeric()) {
-        let mut chars = word.chars();
-        if let Some(first_char) = chars.next() {
-            let first_upper = first_char.to_uppercase();
-            output.extend(first_upper);
-            output.extend(chars);
-        }
-    }
-    output
+/// A node within a feature or rule.
+#[cfg(any(feature = "messages", feature = "gherkin"))]
+pub(crate) enum Child<'n, 'a> {
+    Background(&'n Scenario<'a>),
+    Scenario(&'n Scenario<'a>),
+    Outline(&'n ScenarioOutline<'a>),
+    Rule(&'n Rule<'a>),
 }
 
-fn calculate_arg_types(example_blocks: &[ExampleBlock]) -> Vec<CSType> {
-    let mut arg_types: Vec<CSType> = vec![];
-    let arg_count = match example_blocks.get(0) {
-        Some(block) => block.labels.entries.len(),
-        None => 0,
-    };
-
-    for i in 0..arg_count {
-        // Find the best type to use for argument i of this test method
-        let best_compatible_type = example_blocks
-            // Iterate over all "Examples:" blocks in this scenario outline
-            .iter()
-            // Lump all the example rows from each block together
-            .flat_map(|block| &block.examples)
-            .map(|row| {
-                row.entries
-                    // For each row, examine the ith entry
-                    .get(i)
-                    .map_or(
-                        // If it's absent, asume it's a string
-                        CSType::String,
-                        // Otherwise, calculate its type.
-                        |arg| CSType::from(&arg),
-                    )
-            })
-            // Combine all the calculated types
-            .reduce(|x, y| x.lowest_common_type(y))
-            // If no types were found (because the blocks were all empty)
-            // assume it is of type String.
-            .unwrap_or(CSType::String);
-
-        arg_types.push(best_compatible_type);
-    }
-    arg_types
-}
-
-impl NUnit {
-    fn escape_literal(&self, literal: &str, add_quotes: bool) -> String {
-        // Remove up to one backslash or forward slash from an unquoted literal, in that order of preference.
-        let literal = if let Some(stripped_of_backslash) = literal.strip_prefix('\\') {
-            stripped_of_backslash
-        } else if let Some(stripped_of_forward_slash) = literal.strip_prefix('/') {
-            stripped_of_forward_slash
-        } else {
-            literal
-        };
-        if add_quotes {
-            // When new wrapping quotes and @ are added to bare words,
-            // any contained quotes need to be doubled to avoid breaking
-            // the verbatime string.
-            format!("@\"{}\"", literal.replace('"', "\"\""))
-        } else {
-            format!("@{}", literal)
-        }
-    }
-
-    fn interpret_arg(&self, arg: &str, cs_type: CSType) -> String {
-        match cs_type {
-            CSType::Bool => {
-                let lowercase = arg.to_ascii_lowercase();
-                if lowercase == "true" {
-                    lowercase
-                } else {
-                    String::from("false")
-                }
-            }
-            CSType::Int64 => arg.to_owned(),
-            CSType::Double => arg.to_owned(),
-            CSType::String => {
-                let already_quoted = arg.starts_with('"')
-                    && arg.ends_with('"')
-                    && arg.chars().filter(|&x| x == '"').count() == 2;
-                let add_quotes = !already_quoted;
-                self.escape_literal(arg, add_quotes)
-            }
-        }
-    }
-
-    fn write_test_case<'a, S: AsRef<str>>(
-        &'a self,
-        arg_types: &'a [CSType],
-        arg_strings: impl Iterator<Item = S>,
-        category: &'a str,
-    ) -> String {
-        let mut output = String::from("    [TestCase(");
-        let mut first = true;
-        for (&arg_type, arg_string) in arg_types.iter().zip(arg_strings) {
-            if !first {
-                output += ", ";
-            }
-            output += &self.interpret_arg(arg_string.as_ref(), arg_type);
-            first = false;
-        }
-        if category != "" {
-            output += ", Category=\"";
-            output += category;
-            output += "\""
+#[cfg(any(feature = "messages", feature = "gherkin"))]
+impl<'n, 'a> Child<'n, 'a> {
+    fn start(&self) -> Location {
+        match self {
+            Child::Background(scenario) | Child::Scenario(scenario) => scenario.span.start,
+            Child::Outline(outline) => outline.span.start,
+            Child::Rule(rule) => rule.span.start,
         }
-        output += ")]\n";
-        output
     }
 }
 
-impl<'a> Export<NUnit> for ScenarioOutline<'a> {
-    fn export(&self, nunit: NUnit) -> String {
-        let mut output = String::new();
-        let arg_types = calculate_arg_types(&self.example_blocks);
-        for block in &self.example_blocks {
-            let comma_separated_tags = block.tags.join(",");
+/// The backgrounds, scenarios and rules within a feature or rule, in file order.
+#[cfg(any(feature = "messages", feature = "gherkin"))]
+pub(crate) fn children<'n, 'a>(
+    background: &'n Option<Scenario<'a>>,
+    items: &'n [FeatureItem<'a>],
+    rules: &'n [Rule<'a>],
+) -> Vec<Child<'n, 'a>> {
+    let mut children: Vec<Child> = background.iter().map(Child::Background).collect();
+    children.extend(items.iter().map(|item| match item {
+        FeatureItem::Bare(scenario) => Child::Scenario(scenario),
+        FeatureItem::Outline(outline) => Child::Outline(outline),
+    }));
+    children.extend(rules.iter().map(Child::Rule));
+    children.sort_by_key(Child::start);
+    children
+}
 
-            for example in &block.examples {
-                let test_case = nunit.write_test_case(
-                    &arg_types,
-                    example.entries.iter(),
-                    &comma_separated_tags,
-                );
-                output += &test_case;
-            }
-        }
-        output += &format!("    public void {}(", pascal(self.name));
-        for (i, arg) in self.example_blocks[0].labels.entries.iter().enumerate() {
-            if i != 0 {
-                output.push_str(", ");
-            }
-            output += arg_types.get(i).unwrap_or(&CSType::String).to_str();
-            output += " ";
-            output += &camel(arg);
-        }
-        output += ")\n";
-        output += "    {\n";
+/// Exports features as the JSON serialization of their AST.
+#[cfg(feature = "json")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Json;
 
-        for step in &self.steps {
-            let step_title = step
-                .literals
-                .iter()
-                .map(|&x| pascal(x))
-                .reduce(|x, y| x + "___" + &y)
-                .unwrap_or(String::from("[Emtpy step text?]"));
-            output += &format!(
-                "        // {kw:?}({title}(",
-                kw = step.keyword,
-                title = step_title
-            );
-            for (i, variable) in step.variables.iter().enumerate() {
-                if i != 0 {
-                    output += ", "
-                }
-                output += &camel(variable);
-            }
-            output += "));\n";
-        }
-        
-        output += "        Assert.Fail(\"Unimplemented test\");";
-        output += "\n";
-        output += "    }\n";
-        output
+#[cfg(feature = "json")]
+impl<'a> Export<Json> for crate::Feature<'a> {
+    fn export(&self, _json: Json) -> String {
+        serde_json::to_string_pretty(self).expect("The AST always serializes to JSON")
     }
 }
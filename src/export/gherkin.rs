@@ -0,0 +1,292 @@
+//! Exporting features as `.feature` files, in a canonical layout.
+//!
+//! Each level of nesting is indented by two spaces, one blank line separates
+//! each background, scenario, examples block and rule from what comes before
+//! it, and the columns of each table are aligned. Blank lines within a
+//! description are kept. Comments are written just before the node or table
+//! row they were attached to, at its indentation.
+
+use super::{children, Child, Export};
+use crate::comment::Comment;
+use crate::error::ParseResult;
+use crate::feature::{ExampleBlock, ExampleRow, Feature, Rule, Scenario, ScenarioOutline};
+use crate::gherkin_tags::{GherkinLine, GherkinLines};
+use crate::location::Location;
+use crate::step::Step;
+use crate::tags::Tag;
+use std::borrow::Cow;
+use unicode_width::UnicodeWidthStr;
+
+/// Exports features as Gherkin, so that any two features which parse the
+/// same are written the same.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Gherkin;
+
+impl Gherkin {
+    /// Whether `formatted`, the export of the feature parsed from `source`, holds
+    /// everything `source` does in the same order, differing only in layout.
+    ///
+    /// It doesn't when the AST has nowhere to keep something, such as a comment
+    /// partway through a description, so a tool rewriting `source` should check
+    /// this first.
+    pub fn preserves(source: &str, formatted: &str) -> bool {
+        match (content(source), content(formatted)) {
+            (Ok(source), Ok(formatted)) => source == formatted,
+            _ => false,
+        }
+    }
+}
+
+/// Everything in a feature file which the layout doesn't change, in order:
+/// each comment, tag, header, step, line of description, doc string and table row.
+fn content(text: &str) -> ParseResult<Vec<String>> {
+    let mut lines = GherkinLines::new(text)?;
+    let mut content: Vec<(Location, String)> = vec![];
+    let mut after_text = false;
+    for (line, span) in &mut lines {
+        let is_text = matches!(line, GherkinLine::FreeText(..));
+        let text = match line {
+            GherkinLine::Tags(tags) => {
+                content.extend(tags.iter().map(tag_content));
+                after_text = false;
+                continue;
+            }
+            GherkinLine::BeginGroup(_, keyword, title, tags) => {
+                content.extend(tags.iter().map(tag_content));
+                format!("{}: {}", keyword, title).trim_end().to_owned()
+            }
+            GherkinLine::StepLine(_, keyword, step_text) => format!("{}{}", keyword, step_text),
+            // Blank lines only belong to a description after its first line.
+            GherkinLine::FreeText(text, blank_lines) if after_text => {
+                "\n".repeat(blank_lines) + text
+            }
+            GherkinLine::FreeText(text, _) => text.to_owned(),
+            GherkinLine::ExampleEntry(row) => match ExampleRow::from_str(row, span) {
+                Ok(row) => format!("{:?}", row.entries),
+                Err(_) => row.to_owned(),
+            },
+            GherkinLine::DocString(doc_string) => format!(
+                "{}{:?}{:?}",
+                doc_string.delimiter, doc_string.content_type, doc_string.content
+            ),
+            GherkinLine::UnterminatedDocString(opening_line) => opening_line.to_owned(),
+        };
+        content.push((span.start, text));
+        after_text = is_text;
+    }
+    content.extend(
+        lines
+            .comments
+            .iter()
+            .map(|comment| (comment.span.start, comment.text.to_string())),
+    );
+    content.sort_by_key(|(start, _)| *start);
+    Ok(content.into_iter().map(|(_, text)| text).collect())
+}
+
+fn tag_content(tag: &Tag) -> (Location, String) {
+    (tag.span.start, format!("@{}", tag.name))
+}
+
+impl<'a> Export<Gherkin> for Feature<'a> {
+    fn export(&self, _gherkin: Gherkin) -> String {
+        let mut printer = Printer::default();
+        printer.feature(self);
+        printer.output
+    }
+}
+
+const INDENT: &str = "  ";
+
+#[derive(Default)]
+struct Printer {
+    output: String,
+}
+
+impl Printer {
+    fn line(&mut self, depth: usize, text: &str) {
+        if !text.is_empty() {
+            self.output += &INDENT.repeat(depth);
+            self.output += text;
+        }
+        self.output += "\n";
+    }
+
+    /// Separates what comes next from what came before with a single blank line.
+    fn blank_line(&mut self) {
+        if !self.output.is_empty() && !self.output.ends_with("\n\n") {
+            self.output += "\n";
+        }
+    }
+
+    fn comments(&mut self, depth: usize, comments: &[Comment]) {
+        for comment in comments {
+            self.line(depth, &comment.text);
+        }
+    }
+
+    fn tags(&mut self, depth: usize, tags: &[Tag]) {
+        if !tags.is_empty() {
+            let names: Vec<_> = tags.iter().map(|tag| format!("@{}", tag.name)).collect();
+            self.line(depth, &names.join(" "));
+        }
+    }
+
+    fn header(&mut self, depth: usize, keyword: &str, name: &str) {
+        self.line(depth, format!("{}: {}", keyword, name).trim_end());
+    }
+
+    fn description(&mut self, depth: usize, description: &[Cow<str>]) {
+        for line in description {
+            self.line(depth, line);
+        }
+    }
+
+    fn feature(&mut self, feature: &Feature) {
+        if feature.language != crate::dialect::DEFAULT_LANGUAGE {
+            self.line(0, &format!("# language: {}", feature.language));
+        }
+        self.comments(0, &feature.comments);
+        self.tags(0, &feature.tags);
+        self.header(0, &feature.keyword, &feature.name);
+        self.description(1, &feature.free_text);
+        self.children(
+            1,
+            children(&feature.background, &feature.items, &feature.rules),
+        );
+        if !feature.trailing_comments.is_empty() {
+            self.blank_line();
+            self.comments(0, &feature.trailing_comments);
+        }
+    }
+
+    fn children(&mut self, depth: usize, children: Vec<Child>) {
+        for child in children {
+            self.blank_line();
+            match child {
+                Child::Background(background) => self.scenario(depth, background),
+                Child::Scenario(scenario) => self.scenario(depth, scenario),
+                Child::Outline(outline) => self.outline(depth, outline),
+                Child::Rule(rule) => self.rule(depth, rule),
+            }
+        }
+    }
+
+    fn rule(&mut self, depth: usize, rule: &Rule) {
+        self.comments(depth, &rule.comments);
+        self.tags(depth, &rule.tags);
+        self.header(depth, &rule.keyword, &rule.name);
+        self.description(depth + 1, &rule.free_text);
+        self.children(depth + 1, children(&rule.background, &rule.items, &[]));
+    }
+
+    fn scenario(&mut self, depth: usize, scenario: &Scenario) {
+        self.comments(depth, &scenario.comments);
+        self.tags(depth, &scenario.tags);
+        self.header(depth, &scenario.keyword, &scenario.name);
+        self.description(depth + 1, &scenario.description);
+        self.steps(depth + 1, &scenario.steps);
+    }
+
+    fn outline(&mut self, depth: usize, outline: &ScenarioOutline) {
+        self.comments(depth, &outline.comments);
+        self.tags(depth, &outline.tags);
+        self.header(depth, &outline.keyword, &outline.name);
+        self.description(depth + 1, &outline.description);
+        self.steps(depth + 1, &outline.steps);
+        for block in &outline.example_blocks {
+            self.blank_line();
+            self.examples(depth + 1, block);
+        }
+    }
+
+    fn examples(&mut self, depth: usize, block: &ExampleBlock) {
+        self.comments(depth, &block.comments);
+        self.tags(depth, &block.tags);
+        self.header(depth, &block.keyword, &block.name);
+        self.description(depth + 1, &block.description);
+        let rows: Vec<_> = std::iter::once(&block.labels)
+            .chain(&block.examples)
+            .collect();
+        self.table(depth + 1, &rows);
+    }
+
+    fn steps(&mut self, depth: usize, steps: &[Step]) {
+        for step in steps {
+            self.comments(depth, &step.comments);
+            self.line(
+                depth,
+                format!("{}{}", step.keyword_text, step.text()).trim_end(),
+            );
+            if let Some(doc_string) = &step.doc_string {
+                let delimiter: &str = &doc_string.delimiter;
+                let escaped_delimiter: String = delimiter.chars().flat_map(|c| ['\\', c]).collect();
+                self.line(
+                    depth + 1,
+                    &format!(
+                        "{}{}",
+                        delimiter,
+                        doc_string.content_type.as_deref().unwrap_or("")
+                    ),
+                );
+                if !doc_string.content.is_empty() {
+                    for line in doc_string.content.split('\n') {
+                        self.line(depth + 1, &line.replace(delimiter, &escaped_delimiter));
+                    }
+                }
+                self.line(depth + 1, delimiter);
+            }
+            if let Some(table) = &step.data_table {
+                let rows: Vec<_> = table.rows.iter().collect();
+                self.table(depth + 1, &rows);
+            }
+        }
+    }
+
+    /// Writes `rows` with their columns aligned, by the width each cell takes up on screen.
+    fn table(&mut self, depth: usize, rows: &[&ExampleRow]) {
+        let cells: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| {
+                row.entries
+                    .iter()
+                    .map(|entry| escape_entry(entry))
+                    .collect()
+            })
+            .collect();
+        let mut widths: Vec<usize> = vec![];
+        for row in &cells {
+            for (column, cell) in row.iter().enumerate() {
+                let width = cell.width();
+                match widths.get_mut(column) {
+                    Some(widest) => *widest = (*widest).max(width),
+                    None => widths.push(width),
+                }
+            }
+        }
+        for (row, cells) in rows.iter().zip(cells) {
+            self.comments(depth, &row.comments);
+            let padded: Vec<_> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - cell.width())))
+                .collect();
+            self.line(depth, &format!("| {} |", padded.join(" | ")));
+        }
+    }
+}
+
+/// Escapes the characters in a table entry which would otherwise be read
+/// as the end of the entry, an escape, or the end of the line.
+fn escape_entry(entry: &str) -> String {
+    let mut escaped = String::with_capacity(entry.len());
+    for c in entry.chars() {
+        match c {
+            '\\' => escaped += "\\\\",
+            '|' => escaped += "\\|",
+            '\n' => escaped += "\\n",
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,434 @@
+//! Exporting features as [Cucumber Messages](https://github.com/cucumber/messages):
+//! newline-delimited JSON envelopes, one per line.
+//!
+//! A feature becomes a `source` envelope holding the feature file, a
+//! `gherkinDocument` envelope holding its AST, and a `pickle` envelope for each
+//! scenario it compiles to. IDs are derived from where nodes are in the
+//! feature file, so exporting the same file twice gives the same output.
+//! Pickles' IDs start with `pickle:`, to keep them apart from their scenarios'.
+//!
+//! The `gherkinDocument` alone can also be exported, in the shape the reference
+//! Cucumber parsers give it, with [`GherkinDocument`].
+
+use super::{children, Child, Export};
+use crate::comment::Comment;
+use crate::feature::{
+    ExampleBlock, ExampleRow, Feature, FeatureItem, Rule, Scenario, ScenarioOutline,
+};
+use crate::gherkin_tags::StepKeyword;
+use crate::location::{Location, Span};
+use crate::pickle::{compile, node_id, Pickle, PickleArgument, PickleStepType};
+use crate::step::Step;
+use crate::tags::Tag;
+use serde_json::{json, Value};
+use std::borrow::Cow;
+
+/// Exports features as Cucumber Messages, given the file they were read from.
+#[derive(Debug, Clone, Copy)]
+pub struct Messages<'s> {
+    /// The path of the feature file, as it should appear in the messages.
+    pub uri: &'s str,
+    /// The contents of the feature file.
+    pub source: &'s str,
+}
+
+/// Exports features as the `gherkinDocument` AST JSON of the reference Cucumber parsers,
+/// given the file they were read from.
+///
+/// Node IDs count up from `0` in the order the reference parsers number nodes when
+/// asked for predictable IDs, so the output can be compared with theirs.
+#[derive(Debug, Clone, Copy)]
+pub struct GherkinDocument<'s> {
+    /// The path of the feature file, as it should appear in the document.
+    pub uri: &'s str,
+    /// The contents of the feature file.
+    pub source: &'s str,
+}
+
+impl<'a, 's> Export<Messages<'s>> for Feature<'a> {
+    fn export(&self, messages: Messages<'s>) -> String {
+        let mut writer = DocumentWriter::new(messages.source, Ids::Locations);
+        let mut envelopes = vec![
+            json!({
+                "source": {
+                    "uri": messages.uri,
+                    "data": messages.source,
+                    "mediaType": "text/x.cucumber.gherkin+plain",
+                }
+            }),
+            json!({ "gherkinDocument": writer.document(self, messages.uri) }),
+        ];
+        envelopes.extend(
+            compile(self)
+                .iter()
+                .map(|p| json!({ "pickle": pickle(p, messages.uri) })),
+        );
+        envelopes
+            .iter()
+            .map(|envelope| envelope.to_string() + "\n")
+            .collect()
+    }
+}
+
+impl<'a, 's> Export<GherkinDocument<'s>> for Feature<'a> {
+    fn export(&self, document: GherkinDocument<'s>) -> String {
+        let mut writer = DocumentWriter::new(document.source, Ids::Sequential(0));
+        json!({ "gherkinDocument": writer.document(self, document.uri) }).to_string() + "\n"
+    }
+}
+
+/// How nodes in a `gherkinDocument` are identified.
+enum Ids {
+    /// By where they were read from, matching the IDs pickles refer to.
+    Locations,
+    /// By counting up, as each node is finished.
+    Sequential(usize),
+}
+
+/// Builds the `gherkinDocument` for a feature. Descriptions and comments are
+/// taken from the source lines, since the AST doesn't keep their indentation.
+struct DocumentWriter<'s> {
+    lines: Vec<&'s str>,
+    ids: Ids,
+}
+
+impl<'s> DocumentWriter<'s> {
+    fn new(source: &'s str, ids: Ids) -> Self {
+        DocumentWriter {
+            lines: source.lines().collect(),
+            ids,
+        }
+    }
+
+    /// Identifies the node read from `span`. Nodes must be identified in
+    /// the order the reference parsers finish them: each node after the
+    /// nodes within it, and tags after everything else in their node.
+    fn id(&mut self, span: Span) -> String {
+        match &mut self.ids {
+            Ids::Locations => node_id(span),
+            Ids::Sequential(next) => {
+                *next += 1;
+                (*next - 1).to_string()
+            }
+        }
+    }
+
+    fn document(&mut self, feature: &Feature, uri: &str) -> Value {
+        json!({
+            "uri": uri,
+            "feature": self.feature(feature),
+            "comments": self.comments(feature),
+        })
+    }
+
+    /// The lines of the description under the header on `header_line`, as written.
+    /// Comments among them are left out, as they belong to the document.
+    fn description(&self, header_line: usize, description: &[Cow<str>]) -> String {
+        let mut remaining = description.iter().filter(|line| !line.is_empty()).count();
+        let mut lines = vec![];
+        for line in self.lines.iter().skip(header_line) {
+            if remaining == 0 {
+                break;
+            }
+            let trimmed = line.trim();
+            if trimmed.starts_with('#') || (trimmed.is_empty() && lines.is_empty()) {
+                continue;
+            }
+            if !trimmed.is_empty() {
+                remaining -= 1;
+            }
+            lines.push(*line);
+        }
+        lines.join("\n")
+    }
+
+    /// Every comment in the file, in the order they were written.
+    fn comments(&self, feature: &Feature) -> Value {
+        let mut all: Vec<&Comment> = feature.comments.iter().collect();
+        all.extend(&feature.trailing_comments);
+        item_comments(&feature.background, &feature.items, &mut all);
+        for rule in &feature.rules {
+            all.extend(&rule.comments);
+            item_comments(&rule.background, &rule.items, &mut all);
+        }
+        all.sort_by_key(|comment| comment.span.start);
+        all.iter()
+            .map(|comment| {
+                let line = comment.span.start.line;
+                // A comment without a span, on line 0, has no source line to take.
+                let text = line.checked_sub(1).and_then(|index| self.lines.get(index));
+                json!({
+                    "location": location(Location::new(line, 1)),
+                    "text": text.copied().unwrap_or(&comment.text),
+                })
+            })
+            .collect()
+    }
+
+    fn tags(&mut self, tags: &[Tag]) -> Value {
+        tags.iter()
+            .map(|tag| {
+                json!({
+                    "location": location(tag.span.start),
+                    "name": format!("@{}", tag.name),
+                    "id": self.id(tag.span),
+                })
+            })
+            .collect()
+    }
+
+    fn feature(&mut self, feature: &Feature) -> Value {
+        let children = self.children(&feature.background, &feature.items, &feature.rules);
+        json!({
+            "location": location(feature.span.start),
+            "language": feature.language,
+            "keyword": feature.keyword,
+            "name": feature.name,
+            "description": self.description(feature.span.start.line, &feature.free_text),
+            "children": children,
+            "tags": self.tags(&feature.tags),
+        })
+    }
+
+    /// The backgrounds, scenarios and rules within a feature or rule, in file order.
+    fn children(
+        &mut self,
+        background: &Option<Scenario>,
+        items: &[FeatureItem],
+        rules: &[Rule],
+    ) -> Value {
+        children(background, items, rules)
+            .into_iter()
+            .map(|child| match child {
+                Child::Background(background) => {
+                    json!({ "background": self.background(background) })
+                }
+                Child::Scenario(scenario) => json!({ "scenario": self.scenario(scenario) }),
+                Child::Outline(outline) => json!({ "scenario": self.outline(outline) }),
+                Child::Rule(rule) => json!({ "rule": self.rule(rule) }),
+            })
+            .collect()
+    }
+
+    fn rule(&mut self, rule: &Rule) -> Value {
+        let children = self.children(&rule.background, &rule.items, &[]);
+        let tags = self.tags(&rule.tags);
+        json!({
+            "id": self.id(rule.span),
+            "location": location(rule.span.start),
+            "tags": tags,
+            "keyword": rule.keyword,
+            "name": rule.name,
+            "description": self.description(rule.span.start.line, &rule.free_text),
+            "children": children,
+        })
+    }
+
+    fn background(&mut self, background: &Scenario) -> Value {
+        let steps = self.steps(&background.steps);
+        json!({
+            "id": self.id(background.span),
+            "location": location(background.span.start),
+            "keyword": background.keyword,
+            "name": background.name,
+            "description": self.description(background.span.start.line, &background.description),
+            "steps": steps,
+        })
+    }
+
+    fn scenario(&mut self, scenario: &Scenario) -> Value {
+        let steps = self.steps(&scenario.steps);
+        let tags = self.tags(&scenario.tags);
+        json!({
+            "id": self.id(scenario.span),
+            "location": location(scenario.span.start),
+            "tags": tags,
+            "keyword": scenario.keyword,
+            "name": scenario.name,
+            "description": self.description(scenario.span.start.line, &scenario.description),
+            "steps": steps,
+            "examples": [],
+        })
+    }
+
+    fn outline(&mut self, outline: &ScenarioOutline) -> Value {
+        let steps = self.steps(&outline.steps);
+        let examples: Value = outline
+            .example_blocks
+            .iter()
+            .map(|block| self.examples(block))
+            .collect();
+        let tags = self.tags(&outline.tags);
+        json!({
+            "id": self.id(outline.span),
+            "location": location(outline.span.start),
+            "tags": tags,
+            "keyword": outline.keyword,
+            "name": outline.name,
+            "description": self.description(outline.span.start.line, &outline.description),
+            "steps": steps,
+            "examples": examples,
+        })
+    }
+
+    fn examples(&mut self, block: &ExampleBlock) -> Value {
+        let table_header = self.table_row(&block.labels);
+        let table_body: Value = block
+            .examples
+            .iter()
+            .map(|row| self.table_row(row))
+            .collect();
+        let tags = self.tags(&block.tags);
+        json!({
+            "id": self.id(block.span),
+            "location": location(block.span.start),
+            "tags": tags,
+            "keyword": block.keyword,
+            "name": block.name,
+            "description": self.description(block.span.start.line, &block.description),
+            "tableHeader": table_header,
+            "tableBody": table_body,
+        })
+    }
+
+    fn table_row(&mut self, row: &ExampleRow) -> Value {
+        json!({
+            "id": self.id(row.span),
+            "location": location(row.span.start),
+            "cells": row
+                .entries
+                .iter()
+                .zip(&row.entry_spans)
+                .map(|(value, span)| json!({ "location": location(span.start), "value": value }))
+                .collect::<Value>(),
+        })
+    }
+
+    fn steps(&mut self, steps: &[Step]) -> Value {
+        steps.iter().map(|step| self.step(step)).collect()
+    }
+
+    fn step(&mut self, step: &Step) -> Value {
+        let keyword_type = match step.keyword {
+            StepKeyword::Given => "Context",
+            StepKeyword::When => "Action",
+            StepKeyword::Then => "Outcome",
+            StepKeyword::And | StepKeyword::But => "Conjunction",
+            StepKeyword::Bullet => "Unknown",
+        };
+        let data_table = step.data_table.as_ref().map(|table| {
+            json!({
+                "location": location(table.rows.first().map_or(step.span.start, |row| row.span.start)),
+                "rows": table.rows.iter().map(|row| self.table_row(row)).collect::<Value>(),
+            })
+        });
+        let mut value = json!({
+            "id": self.id(step.span),
+            "location": location(step.span.start),
+            "keyword": step.keyword_text,
+            "keywordType": keyword_type,
+            "text": step.text(),
+        });
+        if let Some(doc_string) = &step.doc_string {
+            let mut doc_string_value = json!({
+                "location": location(doc_string.span.start),
+                "content": doc_string.content,
+                "delimiter": doc_string.delimiter,
+            });
+            if let Some(content_type) = &doc_string.content_type {
+                doc_string_value["mediaType"] = json!(content_type);
+            }
+            value["docString"] = doc_string_value;
+        }
+        if let Some(data_table) = data_table {
+            value["dataTable"] = data_table;
+        }
+        value
+    }
+}
+
+fn location(location: Location) -> Value {
+    json!({ "line": location.line, "column": location.column })
+}
+
+fn item_comments<'c, 'a>(
+    background: &'c Option<Scenario<'a>>,
+    items: &'c [FeatureItem<'a>],
+    all: &mut Vec<&'c Comment<'a>>,
+) {
+    let mut add = |comments: &'c [Comment<'a>], steps: &'c [Step<'a>]| {
+        all.extend(comments);
+        for step in steps {
+            all.extend(&step.comments);
+            for row in step.data_table.iter().flat_map(|table| &table.rows) {
+                all.extend(&row.comments);
+            }
+        }
+    };
+    if let Some(background) = background {
+        add(&background.comments, &background.steps);
+    }
+    for item in items {
+        match item {
+            FeatureItem::Bare(scenario) => add(&scenario.comments, &scenario.steps),
+            FeatureItem::Outline(outline) => {
+                add(&outline.comments, &outline.steps);
+                for block in &outline.example_blocks {
+                    add(&block.comments, &[]);
+                    for row in std::iter::once(&block.labels).chain(&block.examples) {
+                        add(&row.comments, &[]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn pickle(pickle: &Pickle, uri: &str) -> Value {
+    json!({
+        "id": pickle.id,
+        "uri": uri,
+        "name": pickle.name,
+        "language": pickle.language,
+        "steps": pickle.steps.iter().map(|step| {
+            let mut value = json!({
+                "id": step.id,
+                "text": step.text,
+                "type": match step.step_type {
+                    PickleStepType::Context => "Context",
+                    PickleStepType::Action => "Action",
+                    PickleStepType::Outcome => "Outcome",
+                    PickleStepType::Unknown => "Unknown",
+                },
+                "astNodeIds": step.ast_node_ids,
+            });
+            match &step.argument {
+                Some(PickleArgument::DocString { content_type, content }) => {
+                    let mut doc_string = json!({ "content": content });
+                    if let Some(content_type) = content_type {
+                        doc_string["mediaType"] = json!(content_type);
+                    }
+                    value["argument"] = json!({ "docString": doc_string });
+                }
+                Some(PickleArgument::DataTable(rows)) => {
+                    let rows: Value = rows
+                        .iter()
+                        .map(|row| {
+                            let cells: Value =
+                                row.iter().map(|cell| json!({ "value": cell })).collect();
+                            json!({ "cells": cells })
+                        })
+                        .collect();
+                    value["argument"] = json!({ "dataTable": { "rows": rows } });
+                }
+                None => {}
+            }
+            value
+        }).collect::<Value>(),
+        "tags": pickle.tags.iter().map(|tag| json!({
+            "name": format!("@{}", tag.name),
+            "astNodeId": node_id(tag.span),
+        })).collect::<Value>(),
+        "astNodeIds": pickle.ast_node_ids,
+    })
+}
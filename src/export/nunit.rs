@@ -0,0 +1,777 @@
+//! Exports features as NUnit test fixtures, with a test method for each scenario.
+//!
+//! Each test calls a method for each of its steps. The step methods are
+//! generated as stubs, once for each distinct step, in a partial class which
+//! the fixtures derive from, so that only the bodies of the steps are left to write.
+
+use crate::comment::Comment;
+use crate::export::Export;
+use crate::feature::{ExampleBlock, Feature, Rule, Scenario, ScenarioOutline};
+use crate::step::{DataTable, Step};
+use crate::tags::Tag;
+use crate::visit::{walk_background, walk_feature, walk_rule, Visit};
+use identifier::{identifier, Scope};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+mod identifier;
+mod merge;
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum CSType {
+    Bool,
+    Int64,
+    Double,
+    String,
+}
+
+impl CSType {
+    fn lowest_common_type(self, other: CSType) -> CSType {
+        use CSType::*;
+        match (self, other) {
+            // Types remain the same unless contradicted
+            (x, y) if x == y => x,
+            // If a contradiction occurs, we default back to string
+            _ => String,
+        }
+    }
+    fn from(input: &str) -> CSType {
+        if input.parse::<i64>().is_ok() {
+            CSType::Int64
+        } else if input.parse::<f64>().is_ok() {
+            CSType::Double
+        } else if input.parse::<bool>().is_ok() {
+            CSType::Bool
+        } else {
+            CSType::String
+        }
+    }
+
+    fn to_str(self) -> &'static str {
+        match self {
+            CSType::Bool => "bool",
+            CSType::Int64 => "long",
+            CSType::Double => "double",
+            CSType::String => "string",
+        }
+    }
+}
+/// Exports features as NUnit test fixtures.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NUnit {
+    /// Whether to copy comments from the feature file as `//` comments.
+    pub comments: bool,
+    /// Whether to call the steps of the background at the start of each
+    /// test, rather than from a `[SetUp]` method.
+    pub inline_background: bool,
+    /// Whether to drop the accents from letters in identifiers, so that
+    /// `Café` is named `Cafe`.
+    pub strip_diacritics: bool,
+}
+
+/// Renders `text` as a verbatim C# string literal.
+fn verbatim_literal(text: &str) -> String {
+    format!("@\"{}\"", text.replace('"', "\"\""))
+}
+
+/// Splits `text` around its first `"quoted"` value, which is returned
+/// without its quotes and with any `\"` or `\\` within it unescaped.
+fn split_quoted(text: &str) -> Option<(&str, String, &str)> {
+    let start = text.find('"')?;
+    let mut quoted = String::new();
+    let mut escaping = false;
+    for (i, c) in text[start + 1..].char_indices() {
+        match c {
+            '"' if !escaping => return Some((&text[..start], quoted, &text[start + i + 2..])),
+            '\\' if !escaping => escaping = true,
+            _ => {
+                if escaping && c != '"' && c != '\\' {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+                escaping = false;
+            }
+        }
+    }
+    None
+}
+
+/// Writes the comment marking a generated method, which lets [`NUnit::merge`]
+/// recognise the method when the feature is exported again.
+/// The hash of the method's body is added once the whole feature is written.
+fn marker(key: &str) -> String {
+    format!("    {}{}\n", merge::MARKER, key)
+}
+
+/// Indents every non-empty line of `input` by one level, for nesting generated code.
+fn indent(input: &str) -> String {
+    let mut output = String::new();
+    for line in input.split_inclusive('\n') {
+        if !line.trim().is_empty() {
+            output += "    ";
+        }
+        output += line;
+    }
+    output
+}
+
+fn calculate_arg_types(example_blocks: &[ExampleBlock]) -> Vec<CSType> {
+    let mut arg_types: Vec<CSType> = vec![];
+    let arg_count = match example_blocks.first() {
+        Some(block) => block.labels.entries.len(),
+        None => 0,
+    };
+
+    for i in 0..arg_count {
+        // Find the best type to use for argument i of this test method
+        let best_compatible_type = example_blocks
+            // Iterate over all "Examples:" blocks in this scenario outline
+            .iter()
+            // Lump all the example rows from each block together
+            .flat_map(|block| &block.examples)
+            .map(|row| {
+                row.entries
+                    // For each row, examine the ith entry
+                    .get(i)
+                    .map_or(
+                        // If it's absent, asume it's a string
+                        CSType::String,
+                        // Otherwise, calculate its type.
+                        |arg| CSType::from(arg),
+                    )
+            })
+            // Combine all the calculated types
+            .reduce(|x, y| x.lowest_common_type(y))
+            // If no types were found (because the blocks were all empty)
+            // assume it is of type String.
+            .unwrap_or(CSType::String);
+
+        arg_types.push(best_compatible_type);
+    }
+    arg_types
+}
+
+impl NUnit {
+    fn pascal(&self, name: &str) -> String {
+        identifier::pascal(name, self.strip_diacritics)
+    }
+
+    fn camel(&self, name: &str) -> String {
+        identifier::camel(name, self.strip_diacritics)
+    }
+
+    /// Renders `comments` as C# line comments at the given indentation, if enabled.
+    fn write_comments(&self, comments: &[Comment], indentation: &str) -> String {
+        let mut output = String::new();
+        if self.comments {
+            for comment in comments {
+                output += &format!("{}// {}\n", indentation, comment.content());
+            }
+        }
+        output
+    }
+
+    fn escape_literal(&self, literal: &str, add_quotes: bool) -> String {
+        // Remove up to one backslash or forward slash from an unquoted literal, in that order of preference.
+        let literal = if let Some(stripped_of_backslash) = literal.strip_prefix('\\') {
+            stripped_of_backslash
+        } else if let Some(stripped_of_forward_slash) = literal.strip_prefix('/') {
+            stripped_of_forward_slash
+        } else {
+            literal
+        };
+        if add_quotes {
+            // When new wrapping quotes and @ are added to bare words,
+            // any contained quotes need to be doubled to avoid breaking
+            // the verbatime string.
+            format!("@\"{}\"", literal.replace('"', "\"\""))
+        } else {
+            format!("@{}", literal)
+        }
+    }
+
+    fn interpret_arg(&self, arg: &str, cs_type: CSType) -> String {
+        match cs_type {
+            CSType::Bool => {
+                let lowercase = arg.to_ascii_lowercase();
+                if lowercase == "true" {
+                    lowercase
+                } else {
+                    String::from("false")
+                }
+            }
+            CSType::Int64 => arg.to_owned(),
+            CSType::Double => arg.to_owned(),
+            CSType::String => {
+                let already_quoted = arg.starts_with('"')
+                    && arg.ends_with('"')
+                    && arg.chars().filter(|&x| x == '"').count() == 2;
+                let add_quotes = !already_quoted;
+                self.escape_literal(arg, add_quotes)
+            }
+        }
+    }
+
+    /// Renders `text` as a regular C# string literal.
+    fn string_literal(&self, text: &str) -> String {
+        let escaped = text
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        format!("\"{}\"", escaped)
+    }
+
+    /// Renders a description as a `[Description]` attribute, unless it is empty.
+    fn write_description(&self, description: &[Cow<str>], indentation: &str) -> String {
+        if description.is_empty() {
+            return String::new();
+        }
+        format!(
+            "{}[Description({})]\n",
+            indentation,
+            self.string_literal(&description.join("\n"))
+        )
+    }
+
+    /// Renders a `[Category]` attribute for each tag.
+    fn write_categories(&self, tags: &[Tag], indentation: &str) -> String {
+        let mut output = String::new();
+        for tag in tags {
+            output += &format!(
+                "{}[Category({})]\n",
+                indentation,
+                self.string_literal(&tag.name)
+            );
+        }
+        output
+    }
+
+    /// Splits a step into the method it calls, and the arguments it passes:
+    /// its placeholders, the `"quoted"` values in its text, and its data table
+    /// or doc string. The words on either side of a placeholder or quoted
+    /// value are separated by `___` in the name of the method.
+    ///
+    /// `parameters` are those of the test calling the step. A placeholder
+    /// without one is passed as it's written, like any other text.
+    fn step_call(&self, step: &Step, parameters: &[Parameter]) -> StepCall {
+        let mut pieces = vec![];
+        let mut args = vec![];
+        let mut text = String::new();
+        for (i, literal) in step.literals.iter().enumerate() {
+            let mut rest: &str = literal;
+            while let Some((before, quoted, after)) = split_quoted(rest) {
+                text += before;
+                pieces.push(std::mem::take(&mut text));
+                args.push(StepArg {
+                    value: verbatim_literal(&quoted),
+                    cs_type: "string",
+                    name: String::from("text"),
+                });
+                rest = after;
+            }
+            text += rest;
+            if let Some(variable) = step.variables.get(i) {
+                pieces.push(std::mem::take(&mut text));
+                let parameter = parameters
+                    .iter()
+                    .find(|parameter| parameter.label == *variable);
+                args.push(StepArg {
+                    value: match parameter {
+                        Some(parameter) => parameter.name.clone(),
+                        None => verbatim_literal(&format!("<{}>", variable)),
+                    },
+                    cs_type: parameter.map_or("string", |parameter| parameter.cs_type.to_str()),
+                    name: self.camel(variable),
+                });
+            }
+        }
+        pieces.push(text);
+        if let Some(table) = &step.data_table {
+            args.push(StepArg {
+                value: self.table_literal(table),
+                cs_type: "string[][]",
+                name: String::from("table"),
+            });
+        }
+        // A verbatim literal would carry the lines of the doc string into the
+        // indentation of the code, so its line breaks are escaped instead.
+        if let Some(doc_string) = &step.doc_string {
+            args.push(StepArg {
+                value: self.string_literal(&doc_string.content),
+                cs_type: "string",
+                name: String::from("docString"),
+            });
+        }
+        let words: Vec<_> = pieces.iter().map(|piece| self.pascal(piece)).collect();
+        StepCall {
+            name: identifier(words.join("___")),
+            pattern: pieces
+                .iter()
+                .map(|piece| piece.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect(),
+            args,
+        }
+    }
+
+    /// Renders a data table as a `string[][]` literal.
+    fn table_literal(&self, table: &DataTable) -> String {
+        let rows = table
+            .rows
+            .iter()
+            .map(|row| {
+                let cells = row
+                    .entries
+                    .iter()
+                    .map(|cell| verbatim_literal(cell))
+                    .collect::<Vec<_>>();
+                format!("new string[] {{ {} }}", cells.join(", "))
+            })
+            .collect::<Vec<_>>();
+        format!("new string[][] {{ {} }}", rows.join(", "))
+    }
+
+    fn write_test_case<'a, S: AsRef<str>>(
+        &'a self,
+        arg_types: &'a [CSType],
+        arg_strings: impl Iterator<Item = S>,
+        category: &'a str,
+        name: &'a str,
+        description: &'a [Cow<'a, str>],
+    ) -> String {
+        let mut output = String::from("    [TestCase(");
+        let mut first = true;
+        for (&arg_type, arg_string) in arg_types.iter().zip(arg_strings) {
+            if !first {
+                output += ", ";
+            }
+            output += &self.interpret_arg(arg_string.as_ref(), arg_type);
+            first = false;
+        }
+        if !category.is_empty() {
+            output += ", Category=\"";
+            output += category;
+            output += "\""
+        }
+        if !name.is_empty() {
+            // NUnit fills in `{m}` with the method name and `{a}` with the arguments.
+            output += ", TestName=";
+            output += &self.string_literal(&format!("{{m}} ({}){{a}}", name));
+        }
+        if !description.is_empty() {
+            output += ", Description=";
+            output += &self.string_literal(&description.join("\n"));
+        }
+        output += ")]\n";
+        output
+    }
+}
+
+/// A parameter of the test being written, for a column of its examples.
+struct Parameter {
+    label: String,
+    cs_type: CSType,
+    name: String,
+}
+
+/// A step, as the call of a step method.
+struct StepCall {
+    /// The name of the step method, unless another step already has it.
+    name: String,
+    /// The text on either side of the arguments, which tells apart steps
+    /// whose names are the same.
+    pattern: Vec<String>,
+    args: Vec<StepArg>,
+}
+
+impl StepCall {
+    fn types(&self) -> Vec<&'static str> {
+        self.args.iter().map(|arg| arg.cs_type).collect()
+    }
+}
+
+/// An argument passed by a test to a step method.
+struct StepArg {
+    /// The C# passed for the argument.
+    value: String,
+    cs_type: &'static str,
+    /// The name of the step method's parameter.
+    name: String,
+}
+
+/// A step method, which the tests of a feature call for each step
+/// matching its name and the types of its parameters.
+struct StepStub {
+    name: String,
+    /// The pattern of the steps which call it.
+    pattern: Vec<String>,
+    /// The type and name of each parameter.
+    parameters: Vec<(&'static str, String)>,
+}
+
+impl StepStub {
+    fn new(name: String, call: &StepCall) -> Self {
+        let mut scope = Scope::default();
+        let parameters = call
+            .args
+            .iter()
+            .map(|arg| (arg.cs_type, scope.take(arg.name.clone())))
+            .collect();
+        StepStub {
+            name,
+            pattern: call.pattern.clone(),
+            parameters,
+        }
+    }
+
+    fn types(&self) -> Vec<&'static str> {
+        self.parameters
+            .iter()
+            .map(|(cs_type, _)| *cs_type)
+            .collect()
+    }
+
+    fn write(&self) -> String {
+        let types = self.types();
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|(cs_type, name)| format!("{} {}", cs_type, name))
+            .collect::<Vec<_>>();
+        let mut output = marker(&format!("step {}({})", self.name, types.join(", ")));
+        output += &format!("    public void {}({})\n", self.name, parameters.join(", "));
+        output += "    {\n";
+        output += "        throw new NotImplementedException();\n";
+        output += "    }\n";
+        output
+    }
+}
+
+/// Writes the C# for each node it visits.
+struct Writer {
+    nunit: NUnit,
+    output: String,
+    /// The types of the parameters of the scenario outline being written.
+    arg_types: Vec<CSType>,
+    /// The parameters of the test being written.
+    parameters: Vec<Parameter>,
+    /// The step calls of the backgrounds which apply to the tests being written.
+    background: String,
+    /// The class of step methods which each fixture derives from, once known.
+    steps_class: Option<String>,
+    /// A step method for each distinct step, in the order they're first called.
+    stubs: Vec<StepStub>,
+    /// The name of the rule being written, if any.
+    rule: Option<String>,
+    /// The names taken by the members of the class being written.
+    scope: Scope,
+    /// How many times each marker key has been written so far.
+    keys: HashMap<String, usize>,
+}
+
+impl Writer {
+    fn new(nunit: NUnit) -> Self {
+        Writer {
+            nunit,
+            output: String::new(),
+            arg_types: vec![],
+            parameters: vec![],
+            background: String::new(),
+            steps_class: None,
+            stubs: vec![],
+            rule: None,
+            scope: Scope::default(),
+            keys: HashMap::new(),
+        }
+    }
+
+    /// A scope in which the step methods are taken, so that a test calling
+    /// a step can't call itself, or a parameter instead, by mistake.
+    fn steps_scope(&self) -> Scope {
+        let mut scope = Scope::default();
+        for stub in &self.stubs {
+            scope.reserve(&stub.name);
+        }
+        scope
+    }
+
+    /// The scope of the members of `class`, which can't share its name.
+    fn class_scope(&self, class: &str) -> Scope {
+        let mut scope = self.steps_scope();
+        scope.reserve(class);
+        scope
+    }
+
+    /// The key of the marker of a method written for a node of `kind` named
+    /// `name`, which is unique within the feature. A node whose kind and name
+    /// are the same as earlier nodes' is told apart by how many came before it.
+    fn key(&mut self, kind: &str, name: &str) -> String {
+        let path: Vec<&str> = self
+            .rule
+            .iter()
+            .map(String::as_str)
+            .chain(Some(name))
+            .filter(|name| !name.is_empty())
+            .collect();
+        let key = format!("{} {}", kind, path.join(" / "))
+            .trim_end()
+            .to_owned();
+        let count = self.keys.entry(key.clone()).or_insert(0);
+        *count += 1;
+        match *count {
+            1 => key,
+            count => format!("{} #{}", key, count),
+        }
+    }
+
+    /// The base class declaration of a fixture, deriving from the step methods.
+    fn base_class(&self) -> String {
+        match &self.steps_class {
+            Some(steps_class) => format!(" : {}", steps_class),
+            None => String::new(),
+        }
+    }
+
+    /// Writes a `[SetUp]` method calling the steps of the backgrounds.
+    fn write_set_up(&mut self) {
+        let key = self.key("background", "");
+        self.output += &marker(&key);
+        self.output += "    [SetUp]\n";
+        let name = self.scope.take(String::from("Background"));
+        self.output += &format!("    public void {}()\n", name);
+        self.output += "    {\n";
+        self.output += &self.background;
+        self.output += "    }\n";
+    }
+
+    /// Writes the body of a test method, calling each of `steps`.
+    fn write_body(&mut self, steps: &[Step]) {
+        self.output += "    {\n";
+        if self.nunit.inline_background {
+            self.output += &self.background;
+        }
+        for step in steps {
+            self.visit_step(step);
+        }
+        self.output += "    }\n";
+    }
+}
+
+impl<'ast, 'a> Visit<'ast, 'a> for Writer {
+    fn visit_feature(&mut self, feature: &'ast Feature<'a>) {
+        let nunit = self.nunit;
+        let class = identifier(nunit.pascal(&feature.name));
+        let steps_class = format!("{}Steps", class);
+        self.scope = self.class_scope(&class);
+        // A rule's fixture of the same name would derive from itself.
+        self.scope.reserve(&steps_class);
+        self.steps_class = Some(steps_class);
+        self.output += "using System;\n";
+        self.output += "using NUnit.Framework;\n\n";
+        self.output += &nunit.write_comments(&feature.comments, "");
+        self.output += "[TestFixture]\n";
+        // NUnit applies the categories of a fixture to the tests within it,
+        // so each test only needs the tags of its own.
+        self.output += &nunit.write_categories(&feature.tags, "");
+        self.output += "public class ";
+        self.output += &class;
+        self.output += &self.base_class();
+        self.output += "\n";
+        self.output += "{\n";
+        walk_feature(self, feature);
+        self.output += "\n}\n\n";
+        // The steps are a class of their own, rather than part of the
+        // fixture, so that the nested fixtures of rules can call them too.
+        self.output += &format!("public partial class {}Steps\n", class);
+        self.output += "{\n";
+        let stubs: Vec<_> = self.stubs.iter().map(StepStub::write).collect();
+        self.output += &stubs.join("\n");
+        self.output += "}";
+    }
+
+    fn visit_rule(&mut self, rule: &'ast Rule<'a>) {
+        let nunit = self.nunit;
+        self.output += "\n";
+        self.output += &nunit.write_comments(&rule.comments, "    ");
+        self.output += "    [TestFixture]\n";
+        // A nested class is a fixture of its own, which doesn't share the
+        // categories of the feature's fixture.
+        self.output += &nunit.write_categories(&rule.effective_tags, "    ");
+        let class = self.scope.take(nunit.pascal(&rule.name));
+        self.output += &format!("    public class {}{}\n", class, self.base_class());
+        self.output += "    {\n";
+        let outer = std::mem::take(&mut self.output);
+        let scope = self.class_scope(&class);
+        let outer_scope = std::mem::replace(&mut self.scope, scope);
+        let outer_background = self.background.clone();
+        self.rule = Some(rule.name.to_string());
+        // A nested class doesn't run the `[SetUp]` of the class around it,
+        // so it needs one of its own for the feature's background.
+        if rule.background.is_none() && !self.background.is_empty() && !nunit.inline_background {
+            self.write_set_up();
+        }
+        walk_rule(self, rule);
+        self.background = outer_background;
+        self.scope = outer_scope;
+        self.rule = None;
+        let nested = std::mem::replace(&mut self.output, outer);
+        self.output += &indent(&nested);
+        self.output += "    }\n";
+    }
+
+    /// Adds the steps of the background to those of any enclosing background,
+    /// writing a `[SetUp]` method calling them all unless they're inlined.
+    fn visit_background(&mut self, background: &'ast Scenario<'a>) {
+        let outer = std::mem::take(&mut self.output);
+        self.parameters.clear();
+        self.output += &self.nunit.write_comments(&background.comments, "        ");
+        walk_background(self, background);
+        let steps = std::mem::replace(&mut self.output, outer);
+        self.background += &steps;
+        if !self.nunit.inline_background {
+            self.write_set_up();
+        }
+    }
+
+    fn visit_scenario(&mut self, scenario: &'ast Scenario<'a>) {
+        let nunit = self.nunit;
+        self.output += &nunit.write_comments(&scenario.comments, "    ");
+        let key = self.key("scenario", &scenario.name);
+        self.output += &marker(&key);
+        self.output += "    [Test]\n";
+        self.output += &nunit.write_categories(&scenario.tags, "    ");
+        self.output += &nunit.write_description(&scenario.description, "    ");
+        let name = self.scope.take(nunit.pascal(&scenario.name));
+        self.output += &format!("    public void {}()\n", name);
+        self.parameters.clear();
+        self.write_body(&scenario.steps);
+    }
+
+    fn visit_outline(&mut self, outline: &'ast ScenarioOutline<'a>) {
+        let nunit = self.nunit;
+        self.output += &nunit.write_comments(&outline.comments, "    ");
+        let key = self.key("scenario", &outline.name);
+        self.output += &marker(&key);
+        self.arg_types = calculate_arg_types(&outline.example_blocks);
+        for block in &outline.example_blocks {
+            self.visit_examples(block);
+        }
+        self.output += &nunit.write_categories(&outline.tags, "    ");
+        self.output += &nunit.write_description(&outline.description, "    ");
+        let name = self.scope.take(nunit.pascal(&outline.name));
+        let labels = outline
+            .example_blocks
+            .first()
+            .into_iter()
+            .flat_map(|block| block.labels.entries.iter());
+        let mut scope = self.steps_scope();
+        self.parameters = labels
+            .enumerate()
+            .map(|(i, label)| Parameter {
+                label: label.to_string(),
+                cs_type: self.arg_types.get(i).copied().unwrap_or(CSType::String),
+                name: scope.take(nunit.camel(label)),
+            })
+            .collect();
+        let parameters: Vec<_> = self
+            .parameters
+            .iter()
+            .map(|parameter| format!("{} {}", parameter.cs_type.to_str(), parameter.name))
+            .collect();
+        self.output += &format!("    public void {}({})\n", name, parameters.join(", "));
+        self.write_body(&outline.steps);
+    }
+
+    /// Writes a `[TestCase]` attribute for each example in the block.
+    fn visit_examples(&mut self, block: &'ast ExampleBlock<'a>) {
+        self.output += &self.nunit.write_comments(&block.comments, "    ");
+        self.output += &self.nunit.write_comments(&block.labels.comments, "    ");
+        let comma_separated_tags = block
+            .tags
+            .iter()
+            .map(|tag| tag.name.as_ref())
+            .collect::<Vec<_>>()
+            .join(",");
+        for example in &block.examples {
+            self.output += &self.nunit.write_comments(&example.comments, "    ");
+            let test_case = self.nunit.write_test_case(
+                &self.arg_types,
+                example.entries.iter(),
+                &comma_separated_tags,
+                &block.name,
+                &block.description,
+            );
+            self.output += &test_case;
+        }
+    }
+
+    fn visit_step(&mut self, step: &'ast Step<'a>) {
+        self.output += &self.nunit.write_comments(&step.comments, "        ");
+        for row in step.data_table.iter().flat_map(|table| &table.rows) {
+            self.output += &self.nunit.write_comments(&row.comments, "        ");
+        }
+        let call = self.nunit.step_call(step, &self.parameters);
+        let types = call.types();
+        let known = self
+            .stubs
+            .iter()
+            .find(|stub| stub.pattern == call.pattern && stub.types() == types);
+        let name = match known {
+            Some(stub) => stub.name.clone(),
+            None => {
+                // Steps whose names only differ in punctuation would
+                // otherwise be overloads which can't be told apart.
+                let mut name = call.name.clone();
+                let mut n = 2;
+                while self.steps_class.as_ref() == Some(&name)
+                    || self
+                        .stubs
+                        .iter()
+                        .any(|stub| stub.name == name && stub.types() == types)
+                {
+                    name = format!("{}_{}", call.name, n);
+                    n += 1;
+                }
+                self.stubs.push(StepStub::new(name.clone(), &call));
+                name
+            }
+        };
+        let values: Vec<_> = call.args.iter().map(|arg| arg.value.as_str()).collect();
+        self.output += &format!("        {}({});\n", name, values.join(", "));
+    }
+}
+
+impl<'a> Export<NUnit> for Feature<'a> {
+    fn export(&self, nunit: NUnit) -> String {
+        // The step methods are found first, so that no test is named after one.
+        let mut steps = Writer::new(nunit);
+        steps.visit_feature(self);
+        let mut writer = Writer::new(nunit);
+        writer.stubs = steps.stubs;
+        writer.visit_feature(self);
+        merge::sign(&writer.output)
+    }
+}
+
+impl<'a> Export<NUnit> for Rule<'a> {
+    fn export(&self, nunit: NUnit) -> String {
+        let mut writer = Writer::new(nunit);
+        writer.visit_rule(self);
+        merge::sign(&writer.output)
+    }
+}
+
+impl<'a> Export<NUnit> for Scenario<'a> {
+    fn export(&self, nunit: NUnit) -> String {
+        let mut writer = Writer::new(nunit);
+        writer.visit_scenario(self);
+        writer.output
+    }
+}
+
+impl<'a> Export<NUnit> for ScenarioOutline<'a> {
+    fn export(&self, nunit: NUnit) -> String {
+        let mut writer = Writer::new(nunit);
+        writer.visit_outline(self);
+        writer.output
+    }
+}
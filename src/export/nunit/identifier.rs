@@ -0,0 +1,177 @@
+//! Turning names from feature files into C# identifiers.
+//!
+//! A name is split into words at each character which can't be part of an
+//! identifier, and the words are joined in Pascal or camel case. An identifier
+//! which would start with a digit is prefixed with `_`, and a keyword with `@`.
+
+use std::collections::HashSet;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use unicode_xid::UnicodeXID;
+
+/// The reserved keywords of C#, which can only be identifiers prefixed with `@`.
+/// Contextual keywords such as `var` are identifiers as they are.
+const KEYWORDS: &[&str] = &[
+    "abstract",
+    "as",
+    "base",
+    "bool",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "checked",
+    "class",
+    "const",
+    "continue",
+    "decimal",
+    "default",
+    "delegate",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "event",
+    "explicit",
+    "extern",
+    "false",
+    "finally",
+    "fixed",
+    "float",
+    "for",
+    "foreach",
+    "goto",
+    "if",
+    "implicit",
+    "in",
+    "int",
+    "interface",
+    "internal",
+    "is",
+    "lock",
+    "long",
+    "namespace",
+    "new",
+    "null",
+    "object",
+    "operator",
+    "out",
+    "override",
+    "params",
+    "private",
+    "protected",
+    "public",
+    "readonly",
+    "ref",
+    "return",
+    "sbyte",
+    "sealed",
+    "short",
+    "sizeof",
+    "stackalloc",
+    "static",
+    "string",
+    "struct",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "uint",
+    "ulong",
+    "unchecked",
+    "unsafe",
+    "ushort",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "while",
+];
+
+/// Splits `name` into the words of an identifier. With `strip_diacritics`,
+/// accented letters lose their accents, so that `Café` becomes `Cafe`.
+fn words(name: &str, strip_diacritics: bool) -> Vec<String> {
+    let name: String = if strip_diacritics {
+        name.nfd().filter(|&c| !is_combining_mark(c)).collect()
+    } else {
+        name.nfc().collect()
+    };
+    name.split(|c: char| !c.is_xid_continue() || c == '_')
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first_char) => first_char.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+pub(super) fn pascal(name: &str, strip_diacritics: bool) -> String {
+    words(name, strip_diacritics)
+        .iter()
+        .map(|word| capitalize(word))
+        .collect()
+}
+
+/// Joins the words of `name` in camel case, leaving the first word as written.
+pub(super) fn camel(name: &str, strip_diacritics: bool) -> String {
+    let words = words(name, strip_diacritics);
+    let mut words = words.iter();
+    let mut output = words.next().cloned().unwrap_or_default();
+    output.extend(words.map(|word| capitalize(word)));
+    output
+}
+
+/// Prefixes `name` with `_` if it doesn't start with a letter or `_`,
+/// including if it's empty.
+fn valid_start(name: String) -> String {
+    match name.chars().next() {
+        Some(c) if c.is_xid_start() || c == '_' => name,
+        _ => format!("_{}", name),
+    }
+}
+
+fn escape_keyword(name: String) -> String {
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("@{}", name)
+    } else {
+        name
+    }
+}
+
+/// Makes `name`, as returned by [`pascal`] or [`camel`], a valid identifier.
+pub(super) fn identifier(name: String) -> String {
+    escape_keyword(valid_start(name))
+}
+
+/// The names already taken within a class or method, so that each name given
+/// out is distinct from them.
+#[derive(Default)]
+pub(super) struct Scope {
+    taken: HashSet<String>,
+}
+
+impl Scope {
+    pub(super) fn reserve(&mut self, name: &str) {
+        self.taken.insert(name.trim_start_matches('@').to_owned());
+    }
+
+    /// Takes `name` as an identifier if it's free, or else the first of
+    /// `name_2`, `name_3` and so on which is.
+    pub(super) fn take(&mut self, name: String) -> String {
+        let name = valid_start(name);
+        let mut unique = name.clone();
+        let mut n = 2;
+        while !self.taken.insert(unique.clone()) {
+            unique = format!("{}_{}", name, n);
+            n += 1;
+        }
+        escape_keyword(unique)
+    }
+}
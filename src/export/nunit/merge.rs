@@ -0,0 +1,442 @@
+//! Exporting a feature again over code exported before, keeping the bodies
+//! which developers have written for the methods since.
+//!
+//! Each generated method is preceded by a marker comment naming what it was
+//! generated for, along with a hash of the body it was generated with, such
+//! as `// gherkin[9c3f...]: scenario Feed the yak`. A method whose marker is
+//! still generated has its attributes and signature regenerated around its
+//! old body, if the body has been edited since, or is regenerated whole if
+//! not. A method whose marker isn't generated any more is kept, flagged as
+//! removed, after the method which came before it.
+
+use super::NUnit;
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+pub(super) const MARKER: &str = "// gherkin: ";
+/// The start of a marker, before the hash of the body, if any.
+const MARKER_START: &str = "// gherkin";
+const REMOVED: &str = "removed ";
+
+/// A generated method, from its marker to the end of its body.
+struct Method<'s> {
+    /// What the method was generated for, such as `scenario Feed the yak`.
+    key: &'s str,
+    /// Whether the method has already been flagged as removed.
+    removed: bool,
+    /// The hash of the body the method was generated with, unless it was
+    /// generated before bodies were hashed.
+    hash: Option<&'s str>,
+    /// The marker, attributes and signature.
+    header: Vec<&'s str>,
+    /// The lines from the opening brace of the body to its closing brace.
+    /// A brace on the same line as the signature starts a line of its own.
+    body: Vec<Cow<'s, str>>,
+}
+
+enum Piece<'s> {
+    Line(&'s str),
+    Method(Method<'s>),
+}
+
+impl<'s> Piece<'s> {
+    /// Whether `self` and `other` are the same line, or generated for the same thing.
+    fn matches(&self, other: &Piece) -> bool {
+        match (self, other) {
+            (Piece::Line(line), Piece::Line(other)) => line == other,
+            (Piece::Method(method), Piece::Method(other)) => method.key == other.key,
+            _ => false,
+        }
+    }
+}
+
+impl<'s> Method<'s> {
+    /// Whether the body is no longer the one the method was generated with.
+    fn edited(&self) -> bool {
+        self.hash != Some(&body_hash(&self.body))
+    }
+
+    /// The name of the method, from the last line of its signature.
+    fn name(&self) -> Option<&'s str> {
+        let signature = self.header.last()?;
+        let before_parameters = signature[..signature.find('(')?].trim_end();
+        let start = before_parameters
+            .rfind(|c: char| !c.is_alphanumeric() && c != '_')
+            .map_or(0, |space| space + 1);
+        Some(&before_parameters[start..])
+    }
+}
+
+/// Where a piece of the merged code comes from.
+enum Source {
+    Old(usize),
+    New(usize),
+    Both(usize, usize),
+}
+
+impl NUnit {
+    /// Merges `generated`, a feature just exported as NUnit, into `existing`,
+    /// the code exported for the feature before.
+    ///
+    /// Methods keep the bodies they have in `existing` if they've been edited,
+    /// and are otherwise regenerated, so that tests call any steps added since.
+    /// The attributes and signatures of methods are taken from `generated`,
+    /// so that `[TestCase]`s follow the examples. New methods are added as
+    /// generated. Methods which aren't generated any more are kept with a
+    /// marker saying so. Tests among them are ignored, and renamed if a new
+    /// method has taken their name. Lines outside the generated methods which
+    /// have been added since, such as fields, helper methods and `using`s, are
+    /// kept as they are, unless generated lines in the same place have changed,
+    /// in which case they're taken to be generated lines gone stale.
+    pub fn merge(existing: &str, generated: &str) -> String {
+        let old = split(existing);
+        let new = split(generated);
+        let mut lines: Vec<String> = vec![];
+        // The names of the methods written, or still to be written.
+        let mut names: HashSet<String> = new
+            .iter()
+            .filter_map(|piece| match piece {
+                Piece::Method(method) => method.name().map(str::to_owned),
+                Piece::Line(_) => None,
+            })
+            .collect();
+        // The pieces found in only one of `old` and `new` since the last piece found in both.
+        let mut old_only: Vec<&Piece> = vec![];
+        let mut new_only: Vec<&Piece> = vec![];
+        for source in align(&old, &new) {
+            match source {
+                Source::Old(i) => old_only.push(&old[i]),
+                Source::New(j) => new_only.push(&new[j]),
+                Source::Both(i, j) => {
+                    write_changes(&old_only, &new_only, &mut names, &mut lines);
+                    old_only.clear();
+                    new_only.clear();
+                    match (&old[i], &new[j]) {
+                        (Piece::Method(old), Piece::Method(new)) => {
+                            let body = if old.edited() { &old.body } else { &new.body };
+                            lines.extend(new.header.iter().map(|line| line.to_string()));
+                            lines.extend(body.iter().map(|line| line.to_string()));
+                        }
+                        (_, piece) => write(piece, &mut lines),
+                    }
+                }
+            }
+        }
+        write_changes(&old_only, &new_only, &mut names, &mut lines);
+        lines.join("\n")
+    }
+}
+
+/// A hash of the lines of a method body, which stays the same from one
+/// version of Rust to the next, unlike the hashes of the standard library.
+fn body_hash(body: &[Cow<str>]) -> String {
+    // 64-bit FNV-1a.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (i, line) in body.iter().enumerate() {
+        let separator: &[u8] = if i == 0 { b"" } else { b"\n" };
+        for &byte in separator.iter().chain(line.as_bytes()) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// Adds a hash of the body of each method in `code` to its marker, so that
+/// [`NUnit::merge`] can tell later whether the body has been edited.
+pub(super) fn sign(code: &str) -> String {
+    let mut lines = vec![];
+    for piece in split(code) {
+        match piece {
+            Piece::Line(line) => lines.push(line.to_string()),
+            Piece::Method(method) => {
+                let indentation = indentation(method.header[0]);
+                lines.push(format!(
+                    "{}{}[{}]: {}",
+                    indentation,
+                    MARKER_START,
+                    body_hash(&method.body),
+                    method.key
+                ));
+                write_rest(&method, &mut lines);
+            }
+        }
+    }
+    let mut signed = lines.join("\n");
+    if code.ends_with('\n') {
+        signed.push('\n');
+    }
+    signed
+}
+
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Pairs up the pieces of `old` and `new` which match, keeping as many as
+/// possible in order, with the pieces of each which don't match between them.
+fn align(old: &[Piece], new: &[Piece]) -> Vec<Source> {
+    // `common[i][j]` is how many pieces `old[i..]` and `new[j..]` have in common.
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i].matches(&new[j]) {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut sources = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i].matches(&new[j]) {
+            sources.push(Source::Both(i, j));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            sources.push(Source::Old(i));
+            i += 1;
+        } else {
+            sources.push(Source::New(j));
+            j += 1;
+        }
+    }
+    sources
+}
+
+/// Writes the pieces found in only one of the old and new code, between two
+/// pieces found in both. Old methods are kept, flagged as removed. Old lines
+/// are kept too, unless new lines have been generated in their place.
+fn write_changes(
+    old_only: &[&Piece],
+    new_only: &[&Piece],
+    names: &mut HashSet<String>,
+    lines: &mut Vec<String>,
+) {
+    let regenerated = new_only
+        .iter()
+        .any(|piece| matches!(piece, Piece::Line(line) if !line.trim().is_empty()));
+    for piece in old_only {
+        match piece {
+            Piece::Method(method) => write_removed(method, names, lines),
+            Piece::Line(line) if !regenerated => lines.push(line.to_string()),
+            Piece::Line(_) => {}
+        }
+    }
+    for piece in new_only {
+        write(piece, lines);
+    }
+}
+
+fn write(piece: &Piece, lines: &mut Vec<String>) {
+    match piece {
+        Piece::Line(line) => lines.push(line.to_string()),
+        Piece::Method(method) => {
+            lines.extend(method.header.iter().map(|line| line.to_string()));
+            lines.extend(method.body.iter().map(|line| line.to_string()));
+        }
+    }
+}
+
+/// Writes `method`, flagging it as removed if it isn't already. A removed test
+/// whose name has been taken since is renamed, as C# won't compile two tests
+/// of the same name. Step methods are told apart by their parameters as well
+/// as their names, so a step method with the same name as a new one is left as it is.
+fn write_removed(method: &Method, names: &mut HashSet<String>, lines: &mut Vec<String>) {
+    let marker = method.header[0];
+    if method.removed {
+        lines.push(marker.to_string());
+    } else {
+        lines.push(format!(
+            "{}{}{}{}",
+            indentation(marker),
+            MARKER,
+            REMOVED,
+            method.key
+        ));
+    }
+    let is_test = method.key.starts_with("scenario ");
+    if is_test && !method.removed {
+        lines.push(format!(
+            "{}[Ignore(\"The scenario was removed from the feature file\")]",
+            indentation(marker)
+        ));
+    }
+    let start = lines.len();
+    write_rest(method, lines);
+    let name = match method.name() {
+        Some(name) if is_test => name,
+        _ => return,
+    };
+    let mut renamed = name.to_owned();
+    let mut count = 1;
+    while names.contains(&renamed) {
+        count += 1;
+        renamed = format!("{}_{}", name, count);
+    }
+    if renamed != name {
+        let signature = start + method.header.len() - 2;
+        lines[signature] =
+            lines[signature].replacen(&format!("{}(", name), &format!("{}(", renamed), 1);
+    }
+    names.insert(renamed);
+}
+
+/// Writes `method`, after its marker.
+fn write_rest(method: &Method, lines: &mut Vec<String>) {
+    lines.extend(method.header[1..].iter().map(|line| line.to_string()));
+    lines.extend(method.body.iter().map(|line| line.to_string()));
+}
+
+/// Splits `code` into the generated methods within it, and the lines between them.
+fn split(code: &str) -> Vec<Piece<'_>> {
+    let lines: Vec<&str> = code.lines().collect();
+    let mut pieces = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        match read_method(&lines[i..]) {
+            Some((method, length)) => {
+                pieces.push(Piece::Method(method));
+                i += length;
+            }
+            None => {
+                pieces.push(Piece::Line(lines[i]));
+                i += 1;
+            }
+        }
+    }
+    pieces
+}
+
+/// Reads the method marked by the first of `lines`, returning it along with
+/// the number of lines it takes up.
+fn read_method<'s>(lines: &[&'s str]) -> Option<(Method<'s>, usize)> {
+    let (hash, key) = read_marker(lines.first()?)?;
+    let (removed, key) = match key.strip_prefix(REMOVED) {
+        Some(key) => (true, key),
+        None => (false, key),
+    };
+    // The body starts at the first brace after the marker, whether on a line
+    // of its own or at the end of the signature.
+    let mut scanner = BraceScanner::default();
+    let (body_start, brace) = lines.iter().enumerate().skip(1).find_map(|(i, line)| {
+        scanner.scan(line);
+        scanner.opened_at.map(|brace| (i, brace))
+    })?;
+    let body_end = match scanner.depth {
+        0 => body_start + 1,
+        _ => lines[body_start + 1..]
+            .iter()
+            .position(|line| scanner.scan(line) == 0)
+            .map_or(lines.len(), |end| body_start + end + 2),
+    };
+    let start_line = lines[body_start];
+    let indentation = &start_line[..start_line.len() - start_line.trim_start().len()];
+    let mut header = lines[..body_start].to_vec();
+    let mut body = vec![];
+    if start_line[..brace].trim().is_empty() {
+        body.push(Cow::Borrowed(start_line));
+    } else {
+        header.push(start_line[..brace].trim_end());
+        body.push(Cow::Owned(format!(
+            "{}{}",
+            indentation,
+            &start_line[brace..]
+        )));
+    }
+    body.extend(
+        lines[body_start + 1..body_end]
+            .iter()
+            .map(|&line| Cow::Borrowed(line)),
+    );
+    let method = Method {
+        key,
+        removed,
+        hash,
+        header,
+        body,
+    };
+    Some((method, body_end))
+}
+
+/// Reads a marker, with or without the hash of a body, returning the hash and the key.
+fn read_marker(line: &str) -> Option<(Option<&str>, &str)> {
+    let rest = line.trim().strip_prefix(MARKER_START)?;
+    let (hash, rest) = match rest.strip_prefix('[') {
+        Some(rest) => {
+            let (hash, rest) = rest.split_once(']')?;
+            (Some(hash), rest)
+        }
+        None => (None, rest),
+    };
+    Some((hash, rest.strip_prefix(':')?.trim()))
+}
+
+/// Counts the depth of the braces in C# code, skipping over those in strings,
+/// characters and comments.
+#[derive(Default)]
+struct BraceScanner {
+    depth: usize,
+    state: State,
+    /// Where the first brace opening a block was found, in bytes into the
+    /// line it was found on, once one has been found.
+    opened_at: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum State {
+    #[default]
+    Code,
+    String,
+    VerbatimString,
+    Char,
+    BlockComment,
+}
+
+impl BraceScanner {
+    /// Scans the next line, returning the depth at the end of it.
+    fn scan(&mut self, line: &str) -> usize {
+        let mut chars = line.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            match (self.state, c) {
+                (State::Code, '{') => {
+                    if self.depth == 0 && self.opened_at.is_none() {
+                        self.opened_at = Some(index);
+                    }
+                    self.depth += 1;
+                }
+                (State::Code, '}') => self.depth = self.depth.saturating_sub(1),
+                (State::Code, '/') if matches!(chars.peek(), Some((_, '/'))) => break,
+                (State::Code, '/') if matches!(chars.peek(), Some((_, '*'))) => {
+                    chars.next();
+                    self.state = State::BlockComment;
+                }
+                (State::Code, '@') if matches!(chars.peek(), Some((_, '"'))) => {
+                    chars.next();
+                    self.state = State::VerbatimString;
+                }
+                (State::Code, '"') => self.state = State::String,
+                (State::Code, '\'') => self.state = State::Char,
+                (State::String, '\\') | (State::Char, '\\') => {
+                    chars.next();
+                }
+                (State::String, '"') | (State::Char, '\'') => self.state = State::Code,
+                // `""` within a verbatim string is an escaped quote.
+                (State::VerbatimString, '"') if matches!(chars.peek(), Some((_, '"'))) => {
+                    chars.next();
+                }
+                (State::VerbatimString, '"') => self.state = State::Code,
+                (State::BlockComment, '*') if matches!(chars.peek(), Some((_, '/'))) => {
+                    chars.next();
+                    self.state = State::Code;
+                }
+                _ => {}
+            }
+        }
+        if matches!(self.state, State::String | State::Char) {
+            self.state = State::Code;
+        }
+        self.depth
+    }
+}
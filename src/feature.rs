@@ -1,37 +1,97 @@
-use anyhow::{bail, Context, Result};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::borrow::Cow;
 use std::str;
 
-use crate::export::pascal;
-use crate::export::Export;
-use crate::gherkin_tags::GherkinLine;
+use crate::comment::Comment;
+use crate::dialect::DEFAULT_LANGUAGE;
+use crate::error::{Diagnostics, ParseError, ParseResult};
 use crate::gherkin_tags::GroupingKeyword;
-use crate::{step::Step, NUnit};
+use crate::gherkin_tags::{GherkinLine, GherkinLines, Token};
+use crate::location::{Location, Span};
+use crate::step::Step;
+use crate::tags::{inherit_tags, Tag};
 
-type ParseOutcome<'a, T> = (T, Option<GherkinLine<'a>>);
+pub(crate) type ParseOutcome<'a, T> = (T, Option<Token<'a>>);
 
-pub(crate) trait ParseGherkin<'a> {
-    fn from_lines(
-        title: &'a str,
-        lines: impl Iterator<Item = GherkinLine<'a>>,
-    ) -> Result<ParseOutcome<'a, Self>>
-    where
-        Self: Sized;
+/// The start of a node, along with the comments attached to it.
+pub(crate) type CommentSlot<'b, 'a> = (Location, &'b mut Vec<Comment<'a>>);
 
-    fn from_str_lines(
-        title: &'a str,
-        lines: impl Iterator<Item = &'a str>,
-    ) -> Result<ParseOutcome<'a, Self>>
-    where
-        Self: Sized,
-    {
-        Self::from_lines(title, lines.map(GherkinLine::from_str))
+fn unexpected_line((line, span): Token, expected: &'static str) -> ParseError {
+    ParseError::UnexpectedLine {
+        span,
+        expected,
+        found: line.describe(),
+    }
+}
+
+/// Skips ahead to the next group header, where parsing can resume after an error.
+fn resynchronize<'a>(mut lines: impl Iterator<Item = Token<'a>>) -> Option<Token<'a>> {
+    use GroupingKeyword::*;
+    lines.find(|(line, _)| {
+        matches!(
+            line,
+            GherkinLine::BeginGroup(
+                Scenario | ScenarioOutline | Background | Examples | Rule,
+                ..
+            )
+        )
+    })
+}
+
+/// Reports a group which failed to parse, and resynchronizes after it.
+fn recover<'a, T>(
+    outcome: ParseResult<ParseOutcome<'a, T>>,
+    lines: impl Iterator<Item = Token<'a>>,
+    diagnostics: &mut Diagnostics,
+) -> ParseResult<(Option<T>, Option<Token<'a>>)> {
+    match outcome {
+        Ok((node, next_line)) => Ok((Some(node), next_line)),
+        Err(error) => {
+            diagnostics.report(error)?;
+            Ok((None, resynchronize(lines)))
+        }
+    }
+}
+
+/// Reads the free text following a group header, extending `span` to cover it.
+/// Returns the description along with the first line after it.
+fn read_description<'a>(
+    mut lines: impl Iterator<Item = Token<'a>>,
+    span: &mut Span,
+) -> (Vec<Cow<'a, str>>, Option<Token<'a>>) {
+    let mut description = vec![];
+    loop {
+        match lines.next() {
+            Some((GherkinLine::FreeText(text, blank_lines), text_span)) => {
+                push_description_line(&mut description, text, blank_lines);
+                *span = span.to(text_span);
+            }
+            other_line => return (description, other_line),
+        }
     }
 }
 
-pub trait ParseStr<'a> {
-    fn from_str(input: &'a str) -> Result<Self>
+/// Adds a line of text to a description, keeping the blank lines
+/// between it and the text before it as empty lines.
+fn push_description_line<'a>(
+    description: &mut Vec<Cow<'a, str>>,
+    text: &'a str,
+    blank_lines: usize,
+) {
+    if !description.is_empty() {
+        description.extend(std::iter::repeat_n(Cow::Borrowed(""), blank_lines));
+    }
+    description.push(text.into());
+}
+
+pub(crate) trait ParseGherkin<'a> {
+    fn from_lines(
+        keyword: &'a str,
+        title: &'a str,
+        span: Span,
+        lines: impl Iterator<Item = Token<'a>>,
+        diagnostics: &mut Diagnostics,
+    ) -> ParseResult<ParseOutcome<'a, Self>>
     where
         Self: Sized;
 }
@@ -39,10 +99,27 @@ pub trait ParseStr<'a> {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ExampleRow<'a> {
     pub entries: Vec<Cow<'a, str>>,
+    /// Where each of `entries` was read from.
+    #[serde(default)]
+    pub entry_spans: Vec<Span>,
+    #[serde(default)]
+    pub comments: Vec<Comment<'a>>,
+    #[serde(default)]
+    pub span: Span,
 }
 
 impl<'a> ExampleRow<'a> {
-    pub fn from_str(input: &'a str) -> Result<Self> {
+    /// Copies any text borrowed from the input.
+    pub fn into_owned(self) -> ExampleRow<'static> {
+        ExampleRow {
+            entries: owned_all(self.entries),
+            entry_spans: self.entry_spans,
+            comments: self.comments.into_iter().map(Comment::into_owned).collect(),
+            span: self.span,
+        }
+    }
+
+    pub fn from_str(input: &'a str, span: Span) -> ParseResult<Self> {
         // Record whether any escapes occurred, so that we
         // can go back and replace them.
         let mut ever_escaped = false;
@@ -81,228 +158,643 @@ impl<'a> ExampleRow<'a> {
         // so that it contains the unescaped version.
         if ever_escaped {
             for entry in &mut entries {
-                if entry.contains("\\|") {
-                    *entry = Cow::Owned(entry.replace("\\|", "|"));
+                if entry.contains('\\') {
+                    *entry = Cow::Owned(unescape_entry(entry));
                 }
             }
         }
-        entries.pop().with_context(|| {
-            format!(
-                "This example row seems to be malformed, containing less than two pipes:\n{}",
-                input
-            )
+        entries.pop().ok_or_else(|| ParseError::MalformedTableRow {
+            span,
+            row: input.to_owned(),
         })?;
-        Ok(ExampleRow { entries })
+        let entry_spans = locate_entries(input, span);
+        Ok(ExampleRow {
+            entries,
+            entry_spans,
+            comments: vec![],
+            span,
+        })
     }
 }
 
+/// Copies `text` if it's borrowed, so that it no longer borrows from the input.
+pub(crate) fn owned(text: Cow<str>) -> Cow<'static, str> {
+    Cow::Owned(text.into_owned())
+}
+
+/// Copies each of `texts` with [`owned`].
+pub(crate) fn owned_all(texts: Vec<Cow<str>>) -> Vec<Cow<'static, str>> {
+    texts.into_iter().map(owned).collect()
+}
+
+/// Replaces `\|`, `\\` and `\n` in a table entry with what they stand for.
+/// A backslash before any other character is left as it is.
+fn unescape_entry(entry: &str) -> String {
+    let mut unescaped = String::with_capacity(entry.len());
+    let mut chars = entry.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('|') => unescaped.push('|'),
+            Some('\\') => unescaped.push('\\'),
+            Some('n') => unescaped.push('\n'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Finds the span of the trimmed text between each pair of unescaped pipes in a table row.
+fn locate_entries(input: &str, span: Span) -> Vec<Span> {
+    let mut pipes = vec![];
+    let mut escaping = false;
+    for (column, (index, c)) in input.char_indices().enumerate() {
+        if escaping {
+            escaping = false;
+        } else if c == '\\' {
+            escaping = true;
+        } else if c == '|' {
+            pipes.push((column, index));
+        }
+    }
+    pipes
+        .windows(2)
+        .map(|pair| {
+            let ((pipe_column, pipe_index), (_, next_pipe_index)) = (pair[0], pair[1]);
+            let entry = &input[pipe_index + 1..next_pipe_index];
+            let indent = entry.chars().take_while(|c| c.is_whitespace()).count();
+            let start = span.start.column + pipe_column + 1 + indent;
+            let end = start + entry.trim().chars().count();
+            Span::new(
+                Location::new(span.start.line, start),
+                Location::new(span.start.line, end),
+            )
+        })
+        .collect()
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+// The derived code is wrapped below, to work out effective tags once deserialized.
+#[serde(remote = "Self")]
 pub struct Feature<'a> {
-    pub name: &'a str,
-    pub free_text: Vec<&'a str>,
+    pub keyword: Cow<'a, str>,
+    pub name: Cow<'a, str>,
+    #[serde(default)]
+    pub free_text: Vec<Cow<'a, str>>,
+    #[serde(default)]
     pub items: Vec<FeatureItem<'a>>,
+    #[serde(default)]
+    pub rules: Vec<Rule<'a>>,
+    #[serde(default)]
     pub background: Option<Scenario<'a>>,
-    pub tags: Vec<&'a str>,
+    /// Tags on the feature, which every node within it inherits.
+    #[serde(default)]
+    pub tags: Vec<Tag<'a>>,
+    /// The language code from the `# language:` header, or `en` by default.
+    #[serde(default = "default_language")]
+    pub language: Cow<'a, str>,
+    #[serde(default)]
+    pub comments: Vec<Comment<'a>>,
+    /// Comments after the last node in the file.
+    #[serde(default)]
+    pub trailing_comments: Vec<Comment<'a>>,
+    #[serde(default)]
+    pub span: Span,
+}
+
+fn default_language<'a>() -> Cow<'a, str> {
+    Cow::Borrowed(DEFAULT_LANGUAGE)
+}
+
+impl<'a> Serialize for Feature<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Feature::serialize(self, serializer)
+    }
+}
+
+/// Effective tags aren't trusted from the input, since they could have been
+/// left out or have gone stale, but worked out again from the tags of each node.
+impl<'de, 'a> Deserialize<'de> for Feature<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut feature = Feature::deserialize(deserializer)?;
+        feature.inherit_tags();
+        Ok(feature)
+    }
 }
 
 impl<'a> Feature<'a> {
-    pub fn from_str(input: &'a str) -> Result<Self> {
-        let mut lines = input
-            .lines()
-            .map(|l| l.trim())
-            .filter(|l| !l.is_empty() && !l.starts_with('#'));
-        let mut tags = vec![];
-        let mut line = lines.next().context("Feature file was empty.")?;
-        let title = loop {
-            let parsed_line = GherkinLine::from_str(line);
-            match parsed_line {
-                GherkinLine::Tags(gherkin_tags) => tags.extend(gherkin_tags.into_iter()),
-                GherkinLine::BeginGroup(GroupingKeyword::Feature, title) => {
-                    break title;
-                }
-                _ => bail!(
-                    "Unexpected content while parsing feature tags\n{tags}\n\
-                    Expected `Feature: feature_name` or `@tag_1[...@tag_n]`",
-                    tags = line.clone()
-                ),
+    /// Parses a whole feature file, stopping at the first error.
+    // `FromStr` can't be implemented, since the AST borrows from `input`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &'a str) -> ParseResult<Self> {
+        Self::parse(input, &mut Diagnostics::strict())
+    }
+
+    /// Parses as much of `input` as possible, skipping ahead to the next
+    /// scenario, background or examples header whenever an error is found.
+    /// Returns every error found, along with the feature unless it couldn't be read at all.
+    pub fn from_str_recovering(input: &'a str) -> (Option<Self>, Vec<ParseError>) {
+        let mut diagnostics = Diagnostics::recovering();
+        let feature = Self::parse(input, &mut diagnostics);
+        let mut errors = diagnostics.errors;
+        match feature {
+            Ok(feature) => (Some(feature), errors),
+            Err(error) => {
+                errors.push(error);
+                (None, errors)
+            }
+        }
+    }
+
+    /// Copies any text borrowed from the input, so that the feature can
+    /// outlive it, be cached, or be sent to another thread.
+    pub fn into_owned(self) -> Feature<'static> {
+        Feature {
+            keyword: owned(self.keyword),
+            name: owned(self.name),
+            free_text: owned_all(self.free_text),
+            items: self
+                .items
+                .into_iter()
+                .map(FeatureItem::into_owned)
+                .collect(),
+            rules: self.rules.into_iter().map(Rule::into_owned).collect(),
+            background: self.background.map(Scenario::into_owned),
+            tags: self.tags.into_iter().map(Tag::into_owned).collect(),
+            language: owned(self.language),
+            comments: self.comments.into_iter().map(Comment::into_owned).collect(),
+            trailing_comments: self
+                .trailing_comments
+                .into_iter()
+                .map(Comment::into_owned)
+                .collect(),
+            span: self.span,
+        }
+    }
+
+    fn parse(input: &'a str, diagnostics: &mut Diagnostics) -> ParseResult<Self> {
+        Self::parse_lines(GherkinLines::new(input)?, diagnostics)
+    }
+
+    pub(crate) fn parse_lines(
+        mut lines: GherkinLines<'a>,
+        diagnostics: &mut Diagnostics,
+    ) -> ParseResult<Self> {
+        let language = lines.language;
+        let (keyword, title, tags, span) = match lines.next().ok_or(ParseError::Empty)? {
+            (GherkinLine::BeginGroup(GroupingKeyword::Feature, keyword, title, tags), span) => {
+                (keyword, title, tags, span)
+            }
+            token => {
+                return Err(unexpected_line(
+                    token,
+                    "`Feature: feature_name` or `@tag_1[...@tag_n]`",
+                ))
             }
-            line = match lines.next() {
-                Some(l) => l,
-                None => bail!("Unexpected EOF while reading feature tags."),
-            };
         };
-        let (mut feature, next_line) = Self::from_str_lines(title, lines)?;
-        if let Some(line) = next_line {
-            bail!(
-                "Finished parsing content, but then encountered this unexpected line: {:?}",
-                line
-            );
+        let (mut feature, next_line) =
+            Self::from_lines(keyword, title, span, &mut lines, diagnostics)?;
+        if let Some(token) = next_line {
+            diagnostics.report(unexpected_line(token, "the end of the feature"))?;
         }
         feature.tags = tags;
+        feature.language = language.into();
+        feature.attach_comments(lines.comments);
+        feature.inherit_tags();
         Ok(feature)
     }
-}
-
-impl<'a> Export<NUnit> for Feature<'a> {
-    fn export(&self, _nunit: NUnit) -> String {
-        let mut output = String::new();
-        output += "[TestFixture]\n";
-        output += "public class ";
-        output += &pascal(self.name);
-        output += "\n";
-        output += "{\n";
 
-        for item in &self.items {
-            output += &item.export(NUnit);
+    /// Works out the effective tags of every node, from the tags of the nodes enclosing it.
+    ///
+    /// Parsing, deserializing and [`walk_feature_mut`](crate::visit::walk_feature_mut)
+    /// all do this already. Call it after changing any tags by hand, since
+    /// pickles and exported rules take their tags from the effective tags.
+    pub fn inherit_tags(&mut self) {
+        let feature_tags = &self.tags;
+        if let Some(background) = &mut self.background {
+            background.inherit_tags(feature_tags);
         }
+        for item in &mut self.items {
+            item.inherit_tags(feature_tags);
+        }
+        for rule in &mut self.rules {
+            rule.effective_tags = inherit_tags(feature_tags, &rule.tags);
+            if let Some(background) = &mut rule.background {
+                background.inherit_tags(&rule.effective_tags);
+            }
+            for item in &mut rule.items {
+                item.inherit_tags(&rule.effective_tags);
+            }
+        }
+    }
 
-        output += "\n}";
-        output
+    /// Gives each comment to the nearest node following it.
+    fn attach_comments(&mut self, comments: Vec<Comment<'a>>) {
+        let Feature {
+            items,
+            rules,
+            background,
+            comments: feature_comments,
+            trailing_comments,
+            span,
+            ..
+        } = self;
+        let mut slots = vec![(span.start, feature_comments)];
+        if let Some(background) = background {
+            background.comment_slots(&mut slots);
+        }
+        for item in items {
+            item.comment_slots(&mut slots);
+        }
+        for rule in rules {
+            slots.push((rule.span.start, &mut rule.comments));
+            if let Some(background) = &mut rule.background {
+                background.comment_slots(&mut slots);
+            }
+            for item in &mut rule.items {
+                item.comment_slots(&mut slots);
+            }
+        }
+        slots.sort_by_key(|(start, _)| *start);
+        for comment in comments {
+            let index = slots.partition_point(|(start, _)| *start < comment.span.start);
+            match slots.get_mut(index) {
+                Some((_, node_comments)) => node_comments.push(comment),
+                None => trailing_comments.push(comment),
+            }
+        }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum FeatureItem<'a> {
-    Bare(#[serde(borrow)] Scenario<'a>),
-    Outline(#[serde(borrow)] ScenarioOutline<'a>),
+    Bare(Scenario<'a>),
+    Outline(ScenarioOutline<'a>),
+}
+
+impl<'a> FeatureItem<'a> {
+    /// Copies any text borrowed from the input.
+    pub fn into_owned(self) -> FeatureItem<'static> {
+        match self {
+            FeatureItem::Bare(scenario) => FeatureItem::Bare(scenario.into_owned()),
+            FeatureItem::Outline(outline) => FeatureItem::Outline(outline.into_owned()),
+        }
+    }
+
+    fn inherit_tags(&mut self, inherited: &[Tag<'a>]) {
+        match self {
+            FeatureItem::Bare(scenario) => scenario.inherit_tags(inherited),
+            FeatureItem::Outline(outline) => outline.inherit_tags(inherited),
+        }
+    }
+
+    fn comment_slots<'b>(&'b mut self, slots: &mut Vec<CommentSlot<'b, 'a>>) {
+        match self {
+            FeatureItem::Bare(scenario) => scenario.comment_slots(slots),
+            FeatureItem::Outline(outline) => outline.comment_slots(slots),
+        }
+    }
 }
 
 impl<'a> ParseGherkin<'a> for Feature<'a> {
     fn from_lines(
+        keyword: &'a str,
         name: &'a str,
-        mut lines: impl Iterator<Item = GherkinLine<'a>>,
-    ) -> Result<ParseOutcome<'a, Self>>
+        span: Span,
+        mut lines: impl Iterator<Item = Token<'a>>,
+        diagnostics: &mut Diagnostics,
+    ) -> ParseResult<ParseOutcome<'a, Self>>
     where
         Self: Sized,
     {
-        // First, read free text description
         let mut background = None;
         let mut free_text = vec![];
+        let mut span = span;
 
-        let mut tags: Vec<&str> = vec![];
-        let (mut group_kw, mut group_name) = loop {
-            match lines
-                .next()
-                .context("Feature terminated without any scenarios.")?
-            {
-                GherkinLine::FreeText(text) => {
-                    free_text.push(text);
+        // First, read free text description
+        let mut line = lines.next();
+        loop {
+            match line {
+                Some((GherkinLine::FreeText(text, blank_lines), text_span)) => {
+                    push_description_line(&mut free_text, text, blank_lines);
+                    span = span.to(text_span);
                 }
-                GherkinLine::Tags(new_tags) => tags.extend(new_tags.into_iter()),
-                GherkinLine::BeginGroup(group_kw, group_name) => {
-                    break (group_kw, group_name);
+                Some((GherkinLine::BeginGroup(..), _)) => break,
+                Some(token) => {
+                    diagnostics.report(unexpected_line(token, "description text or a scenario"))?;
                 }
-                bad_line => {
-                    bail!(
-                        "Unexpected content in text description for feature `{}` - `{:?}`",
-                        name,
-                        bad_line
-                    )
+                None => {
+                    diagnostics.report(ParseError::UnexpectedEof {
+                        span,
+                        expected: "a scenario",
+                    })?;
+                    break;
                 }
             }
-        };
+            line = lines.next();
+        }
+
         let mut items = vec![];
-        let mut item_tags: Vec<&'a str> = vec![];
-        loop {
-            let line = match group_kw {
+        let mut rules: Vec<Rule<'a>> = vec![];
+        while let Some(token) = line {
+            let (group_kw, group_keyword, group_name, tags, group_span) = match token {
+                (GherkinLine::BeginGroup(kw, keyword, name, tags), span) => {
+                    (kw, keyword, name, tags, span)
+                }
+                token => {
+                    diagnostics.report(unexpected_line(token, "a new scenario"))?;
+                    line = resynchronize(&mut lines);
+                    continue;
+                }
+            };
+            line = match group_kw {
                 GroupingKeyword::ScenarioOutline => {
-                    let (mut data, next_line) = ScenarioOutline::from_lines(group_name, &mut lines)
-                        .context(format!(
-                            "Failed to parse Scenario Outline `{}` in feature {}`",
-                            group_name, name
-                        ))?;
-                    data.tags.extend(tags.drain(..));
-                    items.push(FeatureItem::Outline(data));
+                    let outcome = ScenarioOutline::from_lines(
+                        group_keyword,
+                        group_name,
+                        group_span,
+                        &mut lines,
+                        diagnostics,
+                    );
+                    let (data, next_line) = recover(outcome, &mut lines, diagnostics)?;
+                    if let Some(mut data) = data {
+                        data.tags = tags;
+                        span = span.to(data.span);
+                        match rules.last_mut() {
+                            Some(rule) => {
+                                rule.span = rule.span.to(data.span);
+                                rule.items.push(FeatureItem::Outline(data))
+                            }
+                            None => items.push(FeatureItem::Outline(data)),
+                        }
+                    }
                     next_line
                 }
                 GroupingKeyword::Scenario => {
-                    let (scenario, next_line) = Scenario::from_lines(group_name, &mut lines)?;
-                    items.push(FeatureItem::Bare(scenario));
+                    let outcome = Scenario::from_lines(
+                        group_keyword,
+                        group_name,
+                        group_span,
+                        &mut lines,
+                        diagnostics,
+                    );
+                    let (scenario, next_line) = recover(outcome, &mut lines, diagnostics)?;
+                    if let Some(mut scenario) = scenario {
+                        scenario.tags = tags;
+                        span = span.to(scenario.span);
+                        match rules.last_mut() {
+                            Some(rule) => {
+                                rule.span = rule.span.to(scenario.span);
+                                rule.items.push(FeatureItem::Bare(scenario))
+                            }
+                            None => items.push(FeatureItem::Bare(scenario)),
+                        }
+                    }
                     next_line
                 }
                 GroupingKeyword::Background => {
-                    let (new_background, next_line) = Scenario::from_lines(group_name, &mut lines)?;
-                    background = match background {
-                        None => Some(new_background),
-                        Some(existing) => {
-                            bail!(
-                                "While parsing Feature `{feature}`, encountered \
-                                Background `{background} - but another background \
-                                (`{existing}`) was already declared for that feature.",
-                                feature = name,
-                                background = new_background.name,
-                                existing = existing.name
-                            )
+                    let outcome = Scenario::from_lines(
+                        group_keyword,
+                        group_name,
+                        group_span,
+                        &mut lines,
+                        diagnostics,
+                    );
+                    let (new_background, next_line) = recover(outcome, &mut lines, diagnostics)?;
+                    if let Some(new_background) = new_background {
+                        span = span.to(new_background.span);
+                        // A background declared after a rule belongs to that rule.
+                        let background = match rules.last_mut() {
+                            Some(rule) => {
+                                rule.span = rule.span.to(new_background.span);
+                                &mut rule.background
+                            }
+                            None => &mut background,
+                        };
+                        match background {
+                            Some(existing) => {
+                                diagnostics.report(ParseError::DuplicateBackground {
+                                    span: new_background.span,
+                                    existing: existing.span,
+                                })?;
+                            }
+                            None => *background = Some(new_background),
                         }
-                    };
+                    }
                     next_line
                 }
-                _ => {
-                    bail!(
-                        "Unexpected keyword at top level of feature: `_{:?}_ {}`",
-                        group_kw,
-                        group_name
+                GroupingKeyword::Rule => {
+                    let outcome = Rule::from_lines(
+                        group_keyword,
+                        group_name,
+                        group_span,
+                        &mut lines,
+                        diagnostics,
                     );
-                }
-            };
-
-            if let Some(line) = line {
-                match line {
-                    GherkinLine::Tags(new_tags) => item_tags.extend(new_tags.into_iter()),
-                    GherkinLine::BeginGroup(k, n) => {
-                        group_kw = k;
-                        group_name = n;
-                    }
-                    _ => {
-                        bail!(
-                        "Unexpected content encountered while parsing items of Feature `{}` - `{:?}",
-                        name, line
-                    )
+                    let (rule, next_line) = recover(outcome, &mut lines, diagnostics)?;
+                    if let Some(mut rule) = rule {
+                        rule.tags = tags;
+                        span = span.to(rule.span);
+                        rules.push(rule);
                     }
+                    next_line
                 }
-            } else {
-                break;
-            }
+                _ => {
+                    diagnostics.report(unexpected_line(
+                        (
+                            GherkinLine::BeginGroup(group_kw, group_keyword, group_name, tags),
+                            group_span,
+                        ),
+                        "a scenario, scenario outline, background or rule",
+                    ))?;
+                    resynchronize(&mut lines)
+                }
+            };
         }
 
         // tags are empty because syntactically,
         // the tags are *outside* the feature.
         // The calling context has them cached and can populate them.
         let feature = Feature {
-            name,
+            keyword: keyword.into(),
+            name: name.into(),
             free_text,
             items,
+            rules,
             background,
             tags: vec![],
+            language: default_language(),
+            comments: vec![],
+            trailing_comments: vec![],
+            span,
         };
 
         Ok((feature, None))
     }
 }
 
+/// A `Rule:` block, grouping the scenarios that illustrate a single business rule.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Rule<'a> {
+    pub keyword: Cow<'a, str>,
+    pub name: Cow<'a, str>,
+    #[serde(default)]
+    pub free_text: Vec<Cow<'a, str>>,
+    #[serde(default)]
+    pub items: Vec<FeatureItem<'a>>,
+    #[serde(default)]
+    pub background: Option<Scenario<'a>>,
+    #[serde(default)]
+    pub tags: Vec<Tag<'a>>,
+    /// These tags, along with those inherited from the enclosing nodes.
+    #[serde(default)]
+    pub effective_tags: Vec<Tag<'a>>,
+    #[serde(default)]
+    pub comments: Vec<Comment<'a>>,
+    #[serde(default)]
+    pub span: Span,
+}
+
+impl<'a> Rule<'a> {
+    /// Copies any text borrowed from the input.
+    pub fn into_owned(self) -> Rule<'static> {
+        Rule {
+            keyword: owned(self.keyword),
+            name: owned(self.name),
+            free_text: owned_all(self.free_text),
+            items: self
+                .items
+                .into_iter()
+                .map(FeatureItem::into_owned)
+                .collect(),
+            background: self.background.map(Scenario::into_owned),
+            tags: self.tags.into_iter().map(Tag::into_owned).collect(),
+            effective_tags: self
+                .effective_tags
+                .into_iter()
+                .map(Tag::into_owned)
+                .collect(),
+            comments: self.comments.into_iter().map(Comment::into_owned).collect(),
+            span: self.span,
+        }
+    }
+}
+
+impl<'a> ParseGherkin<'a> for Rule<'a> {
+    fn from_lines(
+        keyword: &'a str,
+        name: &'a str,
+        span: Span,
+        mut lines: impl Iterator<Item = Token<'a>>,
+        _diagnostics: &mut Diagnostics,
+    ) -> ParseResult<ParseOutcome<'a, Self>>
+    where
+        Self: Sized,
+    {
+        // Only the description is read here. The items following a rule
+        // are attached to it by the enclosing feature, up until the next rule.
+        let mut span = span;
+        let (free_text, terminating_line) = read_description(&mut lines, &mut span);
+
+        let rule = Rule {
+            keyword: keyword.into(),
+            name: name.into(),
+            free_text,
+            items: vec![],
+            background: None,
+            tags: vec![],
+            effective_tags: vec![],
+            comments: vec![],
+            span,
+        };
+
+        Ok((rule, terminating_line))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Scenario<'a> {
-    pub name: &'a str,
+    pub keyword: Cow<'a, str>,
+    pub name: Cow<'a, str>,
+    #[serde(default)]
+    pub description: Vec<Cow<'a, str>>,
+    #[serde(default)]
     pub steps: Vec<Step<'a>>,
-    #[serde(borrow)]
-    pub tags: Vec<&'a str>,
+    #[serde(default)]
+    pub tags: Vec<Tag<'a>>,
+    /// These tags, along with those inherited from the enclosing nodes.
+    #[serde(default)]
+    pub effective_tags: Vec<Tag<'a>>,
+    #[serde(default)]
+    pub comments: Vec<Comment<'a>>,
+    #[serde(default)]
+    pub span: Span,
+}
+
+impl<'a> Scenario<'a> {
+    /// Copies any text borrowed from the input.
+    pub fn into_owned(self) -> Scenario<'static> {
+        Scenario {
+            keyword: owned(self.keyword),
+            name: owned(self.name),
+            description: owned_all(self.description),
+            steps: self.steps.into_iter().map(Step::into_owned).collect(),
+            tags: self.tags.into_iter().map(Tag::into_owned).collect(),
+            effective_tags: self
+                .effective_tags
+                .into_iter()
+                .map(Tag::into_owned)
+                .collect(),
+            comments: self.comments.into_iter().map(Comment::into_owned).collect(),
+            span: self.span,
+        }
+    }
+
+    fn inherit_tags(&mut self, inherited: &[Tag<'a>]) {
+        self.effective_tags = inherit_tags(inherited, &self.tags);
+    }
+
+    fn comment_slots<'b>(&'b mut self, slots: &mut Vec<CommentSlot<'b, 'a>>) {
+        slots.push((self.span.start, &mut self.comments));
+        for step in &mut self.steps {
+            step.comment_slots(slots);
+        }
+    }
 }
 
 impl<'a> ParseGherkin<'a> for Scenario<'a> {
     fn from_lines(
+        keyword: &'a str,
         name: &'a str,
-        mut lines: impl Iterator<Item = GherkinLine<'a>>,
-    ) -> Result<ParseOutcome<'a, Self>> {
+        span: Span,
+        mut lines: impl Iterator<Item = Token<'a>>,
+        diagnostics: &mut Diagnostics,
+    ) -> ParseResult<ParseOutcome<'a, Self>> {
         let mut steps = vec![];
         use GherkinLine::*;
+        let mut span = span;
+        let (description, mut line) = read_description(&mut lines, &mut span);
         let terminating_line = loop {
-            match lines.next() {
-                Some(StepLine(kw, step_text)) => {
-                    let step = Step::new(kw, step_text).context(format!(
-                        "Invalid step `{:?} {}` in scenario `{}`",
-                        kw, step_text, name
-                    ))?;
-                    steps.push(step);
+            match line {
+                Some((StepLine(kw, keyword, step_text), step_span)) => {
+                    match Step::from_lines(kw, keyword, step_text, step_span, &mut lines) {
+                        Ok((step, next_line)) => {
+                            span = span.to(step.span);
+                            steps.push(step);
+                            line = next_line;
+                        }
+                        Err(error) => {
+                            diagnostics.report(error)?;
+                            break resynchronize(&mut lines);
+                        }
+                    }
                 }
                 other_line => {
                     break other_line;
@@ -311,95 +803,125 @@ impl<'a> ParseGherkin<'a> for Scenario<'a> {
         };
 
         let scenario = Scenario {
-            name,
+            keyword: keyword.into(),
+            name: name.into(),
+            description,
             steps,
             tags: vec![],
+            effective_tags: vec![],
+            comments: vec![],
+            span,
         };
 
         Ok((scenario, terminating_line))
     }
 }
 
-impl<'a> Export<NUnit> for Scenario<'a> {
-    fn export(&self, _export_format: NUnit) -> String {
-        let mut output = String::new();
-        output.push_str("    [Test]\n");
-        let x = format!("    public void {}()\n", pascal(self.name));
-        output.push_str(&x);
-        output.push_str("    {\n");
-        output.push_str("\n");
-        output.push_str("    }\n");
-        output
-    }
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ExampleBlock<'a> {
+    pub keyword: Cow<'a, str>,
+    /// The title after `Examples:`, which may be empty.
+    #[serde(default)]
+    pub name: Cow<'a, str>,
+    #[serde(default)]
+    pub description: Vec<Cow<'a, str>>,
+    #[serde(default)]
     pub examples: Vec<ExampleRow<'a>>,
     pub labels: ExampleRow<'a>,
-    #[serde(borrow)]
-    pub tags: Vec<&'a str>,
+    #[serde(default)]
+    pub tags: Vec<Tag<'a>>,
+    /// These tags, along with those inherited from the enclosing nodes.
+    #[serde(default)]
+    pub effective_tags: Vec<Tag<'a>>,
+    #[serde(default)]
+    pub comments: Vec<Comment<'a>>,
+    #[serde(default)]
+    pub span: Span,
+}
+
+impl<'a> ExampleBlock<'a> {
+    /// Copies any text borrowed from the input.
+    pub fn into_owned(self) -> ExampleBlock<'static> {
+        ExampleBlock {
+            keyword: owned(self.keyword),
+            name: owned(self.name),
+            description: owned_all(self.description),
+            examples: self
+                .examples
+                .into_iter()
+                .map(ExampleRow::into_owned)
+                .collect(),
+            labels: self.labels.into_owned(),
+            tags: self.tags.into_iter().map(Tag::into_owned).collect(),
+            effective_tags: self
+                .effective_tags
+                .into_iter()
+                .map(Tag::into_owned)
+                .collect(),
+            comments: self.comments.into_iter().map(Comment::into_owned).collect(),
+            span: self.span,
+        }
+    }
 }
 
 impl<'a> ParseGherkin<'a> for ExampleBlock<'a> {
     fn from_lines(
-        title: &'a str,
-        mut lines: impl Iterator<Item = GherkinLine<'a>>,
-    ) -> Result<ParseOutcome<'a, Self>>
+        keyword: &'a str,
+        name: &'a str,
+        span: Span,
+        mut lines: impl Iterator<Item = Token<'a>>,
+        diagnostics: &mut Diagnostics,
+    ) -> ParseResult<ParseOutcome<'a, Self>>
     where
         Self: Sized,
     {
         use GherkinLine::*;
-        // Ensure we are reading `Examples:` and not `Examples: Some other junk`
-        if !title.trim().is_empty() {
-            bail!(
-                "`Examples:` or `Scenarios:` blocks can't have a title, but this one was given: {}",
-                title
-            )
-        }
-
-        let label_line = lines
-            .next()
-            .context("Expected to find the labels for an example table, but got EOF.")?;
+        let mut span = span;
+        let (description, label_line) = read_description(&mut lines, &mut span);
+        let label_line = label_line.ok_or(ParseError::UnexpectedEof {
+            span,
+            expected: "the labels for an example table",
+        })?;
         let labels = match label_line {
-            GherkinLine::ExampleEntry(row) => ExampleRow::from_str(row).context(format!(
-                "Couldn't parse this row of labels for an example table: `{:?}`",
-                label_line
-            ))?,
-            _ => bail!(
-                "Expected to find labels for a data table, got this instead: {:?}",
-                label_line
-            ),
+            (GherkinLine::ExampleEntry(row), row_span) => ExampleRow::from_str(row, row_span)?,
+            token => return Err(unexpected_line(token, "the labels for an example table")),
         };
+        span = span.to(labels.span);
         let mut examples = vec![];
         let terminator = loop {
             match lines.next() {
-                Some(line) => match line {
-                    BeginGroup(_, _) | Tags(_) => {
-                        break Some(line);
+                Some(token) => match token {
+                    (BeginGroup(..), _) => {
+                        break Some(token);
                     }
-                    ExampleEntry(row) => {
-                        let example_row = ExampleRow::from_str(row)
-                            .context(format!("Failed to read example row : `{}`", row))?;
+                    (ExampleEntry(row), row_span) => {
+                        // A bad row is left out, keeping the rest of the table.
+                        let example_row = match ExampleRow::from_str(row, row_span) {
+                            Ok(example_row) => example_row,
+                            Err(error) => {
+                                diagnostics.report(error)?;
+                                continue;
+                            }
+                        };
 
                         if labels.entries.len() != example_row.entries.len() {
-                            bail!(
-                                "Encountered row of length {} in data table, \
-                                    which was not consistent with the number of \
-                                    labels ({}).\n\
-                                    The labels in question are:\n{:?}\n\
-                                    The examples provided were:\n{:?}",
-                                example_row.entries.len(),
-                                labels.entries.len(),
-                                labels.entries,
-                                example_row.entries
-                            )
+                            diagnostics.report(ParseError::InconsistentTableWidth {
+                                span: row_span,
+                                expected: labels.entries.len(),
+                                found: example_row.entries.len(),
+                            })?;
+                            continue;
                         };
 
+                        span = span.to(row_span);
                         examples.push(example_row);
                     }
-                    _ => {
-                        bail!("Did not expect this line inside data table: `{:?}`", line);
+                    token => {
+                        diagnostics.report(unexpected_line(
+                            token,
+                            "another example row, tags or a new group",
+                        ))?;
+                        break resynchronize(&mut lines);
                     }
                 },
                 None => {
@@ -411,9 +933,15 @@ impl<'a> ParseGherkin<'a> for ExampleBlock<'a> {
         // Tags begin as empty since they are specified in the enclosing scenario.
         // The scenario itself will push appropriate tags in from its buffer.
         let example_block = ExampleBlock {
+            keyword: keyword.into(),
+            name: name.into(),
+            description,
             examples,
             labels,
             tags: vec![],
+            effective_tags: vec![],
+            comments: vec![],
+            span,
         };
         Ok((example_block, terminator))
     }
@@ -421,115 +949,154 @@ impl<'a> ParseGherkin<'a> for ExampleBlock<'a> {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ScenarioOutline<'a> {
-    pub name: &'a str,
+    pub keyword: Cow<'a, str>,
+    pub name: Cow<'a, str>,
+    #[serde(default)]
+    pub description: Vec<Cow<'a, str>>,
+    #[serde(default)]
     pub steps: Vec<Step<'a>>,
+    #[serde(default)]
     pub example_blocks: Vec<ExampleBlock<'a>>,
-    #[serde(borrow)]
-    pub tags: Vec<&'a str>,
+    #[serde(default)]
+    pub tags: Vec<Tag<'a>>,
+    /// These tags, along with those inherited from the enclosing nodes.
+    #[serde(default)]
+    pub effective_tags: Vec<Tag<'a>>,
+    #[serde(default)]
+    pub comments: Vec<Comment<'a>>,
+    #[serde(default)]
+    pub span: Span,
+}
+
+impl<'a> ScenarioOutline<'a> {
+    /// Copies any text borrowed from the input.
+    pub fn into_owned(self) -> ScenarioOutline<'static> {
+        ScenarioOutline {
+            keyword: owned(self.keyword),
+            name: owned(self.name),
+            description: owned_all(self.description),
+            steps: self.steps.into_iter().map(Step::into_owned).collect(),
+            example_blocks: self
+                .example_blocks
+                .into_iter()
+                .map(ExampleBlock::into_owned)
+                .collect(),
+            tags: self.tags.into_iter().map(Tag::into_owned).collect(),
+            effective_tags: self
+                .effective_tags
+                .into_iter()
+                .map(Tag::into_owned)
+                .collect(),
+            comments: self.comments.into_iter().map(Comment::into_owned).collect(),
+            span: self.span,
+        }
+    }
+
+    fn inherit_tags(&mut self, inherited: &[Tag<'a>]) {
+        self.effective_tags = inherit_tags(inherited, &self.tags);
+        for block in &mut self.example_blocks {
+            block.effective_tags = inherit_tags(&self.effective_tags, &block.tags);
+        }
+    }
+
+    fn comment_slots<'b>(&'b mut self, slots: &mut Vec<CommentSlot<'b, 'a>>) {
+        slots.push((self.span.start, &mut self.comments));
+        for step in &mut self.steps {
+            step.comment_slots(slots);
+        }
+        for block in &mut self.example_blocks {
+            slots.push((block.span.start, &mut block.comments));
+            for row in std::iter::once(&mut block.labels).chain(&mut block.examples) {
+                slots.push((row.span.start, &mut row.comments));
+            }
+        }
+    }
 }
 
 impl<'a> ParseGherkin<'a> for ScenarioOutline<'a> {
     fn from_lines(
+        keyword: &'a str,
         name: &'a str,
-        mut lines: impl Iterator<Item = GherkinLine<'a>>,
-    ) -> Result<ParseOutcome<'a, Self>>
+        span: Span,
+        mut lines: impl Iterator<Item = Token<'a>>,
+        diagnostics: &mut Diagnostics,
+    ) -> ParseResult<ParseOutcome<'a, Self>>
     where
         Self: Sized,
     {
         use GherkinLine::*;
 
         let mut steps = vec![];
+        let mut span = span;
+        let (description, mut line) = read_description(&mut lines, &mut span);
         let line_after_steps = loop {
-            match lines.next() {
-                Some(StepLine(kw, step_text)) => {
-                    let step = Step::new(kw, step_text).context(format!(
-                        "Invalid step `{:?} {}` in scenario `{}`",
-                        kw, step_text, name
-                    ))?;
-                    steps.push(step);
-                }
-                Some(tag_line @ Tags(_)) => {
-                    break tag_line;
+            match line {
+                Some((StepLine(kw, keyword, step_text), step_span)) => {
+                    match Step::from_lines(kw, keyword, step_text, step_span, &mut lines) {
+                        Ok((step, next_line)) => {
+                            span = span.to(step.span);
+                            steps.push(step);
+                            line = next_line;
+                        }
+                        Err(error) => {
+                            diagnostics.report(error)?;
+                            line = resynchronize(&mut lines);
+                        }
+                    }
                 }
-                Some(group_line @ BeginGroup(_, _)) => {
-                    break group_line;
+                Some(group_line @ (BeginGroup(..), _)) => {
+                    break Some(group_line);
                 }
-                unexpected => {
-                    bail!(
-                        "Unexpected line `{:?}` while reading steps of scenario outline {}. \
-                        Expected to find more steps, or an `Examples:` block.",
-                        unexpected,
-                        name
-                    )
+                Some(token) => {
+                    diagnostics.report(unexpected_line(
+                        token,
+                        "more steps, or an `Examples:` block",
+                    ))?;
+                    line = resynchronize(&mut lines);
                 }
+                // Reported as missing examples below, like an outline followed by another group.
+                None => break None,
             }
         };
 
-        let mut tags = vec![];
         let mut line = line_after_steps;
         let mut example_blocks = vec![];
-
-        let terminating_line = loop {
-            match line {
-                Tags(new_tags) => {
-                    tags.extend(new_tags.into_iter());
-                    if let Some(next_line) = lines.next() {
-                        line = next_line;
-                    } else {
-                        match tags.last() {
-                            Some(last_tag) => {
-                                bail!("Unexpected EOF after reading tag @{}", last_tag)
-                            }
-                            None => bail!("Unexpected EOF after reading tag marker '@'"),
-                        }
-                    }
-                }
-                BeginGroup(group_keyword, group_name) => match group_keyword {
-                    GroupingKeyword::Examples => {
-                        let (mut example_block, next_line) =
-                            ExampleBlock::from_lines(group_name, &mut lines).context(format!(
-                                "Failed to parse example block #{} in Scenario Outline `{}`",
-                                example_blocks.len() + 1,
-                                name
-                            ))?;
-                        example_block.tags.extend(tags.drain(..));
-                        example_blocks.push(example_block);
-                        if let Some(next_line) = next_line {
-                            line = next_line;
-                        } else {
-                            break None;
-                        }
-                    }
-                    _ => {
-                        break Some(line);
-                    }
-                },
-                _ => {
-                    break Some(line);
-                }
+        while let Some((
+            BeginGroup(GroupingKeyword::Examples, keyword, group_name, tags),
+            group_span,
+        )) = line
+        {
+            let outcome =
+                ExampleBlock::from_lines(keyword, group_name, group_span, &mut lines, diagnostics);
+            let (example_block, next_line) = recover(outcome, &mut lines, diagnostics)?;
+            if let Some(mut example_block) = example_block {
+                example_block.tags = tags;
+                span = span.to(example_block.span);
+                example_blocks.push(example_block);
             }
-        };
+            line = next_line;
+        }
+        let terminating_line = line;
+
+        if example_blocks.is_empty() {
+            diagnostics.report(ParseError::MissingExamples {
+                span,
+                outline: name.to_owned(),
+            })?;
+        }
 
         let outline = ScenarioOutline {
-            name,
+            keyword: keyword.into(),
+            name: name.into(),
+            description,
             steps,
             example_blocks,
             tags: vec![],
+            effective_tags: vec![],
+            comments: vec![],
+            span,
         };
 
         Ok((outline, terminating_line))
     }
 }
-
-impl<'a, T> Export<T> for FeatureItem<'a>
-where
-    Scenario<'a>: Export<T>,
-    ScenarioOutline<'a>: Export<T>,
-{
-    fn export(&self, export_format: T) -> String {
-        match self {
-            FeatureItem::Bare(x) => x.export(export_format),
-            FeatureItem::Outline(x) => x.export(export_format),
-        }
-    }
-}
@@ -1,78 +1,266 @@
-use crate::tags::GherkinTags;
-use anyhow::{bail, Result};
+use crate::comment::Comment;
+use crate::dialect::{language_header, Dialect, DEFAULT_LANGUAGE};
+use crate::error::{ParseError, ParseResult};
+use crate::location::{Location, Span};
+use crate::step::DocString;
+use crate::tags::{GherkinTags, Tag};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::iter::Enumerate;
+
+/// A line of a feature file, along with where it was found.
+pub(crate) type Token<'a> = (GherkinLine<'a>, Span);
 
 #[derive(Debug, Clone)]
 pub(crate) enum GherkinLine<'a> {
-    Tags(GherkinTags<'a>),
-    StepLine(StepKeyword, &'a str),
-    BeginGroup(GroupingKeyword, &'a str),
-    FreeText(&'a str),
+    /// Tags which aren't followed by a group header for them to belong to.
+    Tags(Vec<Tag<'a>>),
+    /// A step, with its keyword as written followed by the step text.
+    StepLine(StepKeyword, &'a str, &'a str),
+    /// A group header, with its keyword as written, the title,
+    /// and the tags on the lines before it.
+    BeginGroup(GroupingKeyword, &'a str, &'a str, Vec<Tag<'a>>),
+    /// Any other text, along with how many blank lines came just before it.
+    FreeText(&'a str, usize),
     ExampleEntry(&'a str),
+    DocString(DocString<'a>),
+    /// A doc string delimiter which was never closed, holding the opening line.
+    UnterminatedDocString(&'a str),
 }
 
 impl<'a> GherkinLine<'a> {
-    pub(crate) fn from_str(mut input: &'a str) -> GherkinLine<'a> {
+    pub(crate) fn from_str(
+        mut input: &'a str,
+        start: Location,
+        dialect: &Dialect,
+    ) -> GherkinLine<'a> {
         use GherkinLine::*;
-        use GroupingKeyword::*;
         input = input.trim();
         if let Some((keyword, title)) = input.split_once(':') {
-            let keyword = keyword.trim();
-            let title = title.trim();
-            match keyword {
-                "Scenario" | "Example " => return BeginGroup(Scenario, title),
-                "Examples" | "Scenarios" => return BeginGroup(Examples, title),
-                "Scenario Outline" | "Scenario Template" => {
-                    return BeginGroup(ScenarioOutline, title)
-                }
-                "Feature" => return BeginGroup(Feature, title),
-                "Background" => return BeginGroup(Background, title),
-                _ => {
-                    // Let any other data fall through to other cases
-                }
+            if let Some(group_keyword) = dialect.grouping_keyword(keyword) {
+                return BeginGroup(group_keyword, keyword.trim(), title.trim(), vec![]);
             }
         }
 
-        if let Some((keyword, title)) = input.split_once(' ') {
-            use StepKeyword::*;
-            let keyword = keyword.trim();
-            let title = title.trim();
-            match keyword {
-                "Given" => return StepLine(Given, title),
-                "When" => return StepLine(When, title),
-                "Then" => return StepLine(Then, title),
-                "And" => return StepLine(And, title),
-                "But" => return StepLine(But, title),
-                "*" => return StepLine(Bullet, title),
-                _ => {
-                    // Let unmatched keywords fall through
-                }
-            }
+        if let Some((step_keyword, keyword, title)) = dialect.split_step(input) {
+            return StepLine(step_keyword, keyword, title);
         }
 
         if let Some(("", after_at_sign)) = input.split_once('@') {
-            return Tags(GherkinTags::new(after_at_sign));
+            return Tags(GherkinTags::new(after_at_sign, start).into_iter().collect());
         }
 
         if input.starts_with('|') {
             return ExampleEntry(input);
         }
 
-        return FreeText(input);
+        FreeText(input, 0)
+    }
+
+    /// Describes this line for use in error messages.
+    pub(crate) fn describe(&self) -> String {
+        use GherkinLine::*;
+        match self {
+            Tags(_) => String::from("tags"),
+            StepLine(_, keyword, text) => format!("step `{}{}`", keyword, text),
+            BeginGroup(_, keyword, title, _) => format!("`{}: {}`", keyword, title),
+            FreeText(text, _) => format!("text `{}`", text),
+            ExampleEntry(row) => format!("table row `{}`", row),
+            DocString(_) | UnterminatedDocString(_) => String::from("doc string"),
+        }
+    }
+}
+
+pub(crate) const DOC_STRING_DELIMITERS: [&str; 2] = ["\"\"\"", "```"];
+
+/// Splits feature file content into `GherkinLine`s, skipping blank lines
+/// and setting comments aside. Doc strings are read as a single line, so
+/// their contents are never mistaken for keywords or comments, and tags
+/// are folded into the group header which follows them.
+pub(crate) struct GherkinLines<'a> {
+    lines: Enumerate<std::vec::IntoIter<&'a str>>,
+    pub(crate) language: &'a str,
+    /// The line of the `# language:` header, which isn't a comment.
+    pub(crate) language_line: Option<usize>,
+    /// Every comment read so far, in order.
+    pub(crate) comments: Vec<Comment<'a>>,
+    dialect: &'static Dialect,
+    line_number: usize,
+    /// A line read ahead while looking for the group header after some tags.
+    pending: Option<Token<'a>>,
+}
+
+impl<'a> GherkinLines<'a> {
+    /// Prepares to read `input` in the language named by its `# language:`
+    /// header, which may appear among any comments before the first keyword.
+    pub(crate) fn new(input: &'a str) -> ParseResult<Self> {
+        Self::from_lines(input.lines().collect())
+    }
+
+    /// Prepares to read the lines of a feature file, without their line endings.
+    pub(crate) fn from_lines(lines: Vec<&'a str>) -> ParseResult<Self> {
+        let (language, language_line, dialect) = detect_language(&lines)?;
+        Ok(GherkinLines {
+            lines: lines.into_iter().enumerate(),
+            language,
+            language_line,
+            comments: vec![],
+            dialect,
+            line_number: 0,
+            pending: None,
+        })
+    }
+
+    fn read_doc_string(&mut self, opening_line: &'a str, delimiter: &'a str) -> Token<'a> {
+        let trimmed = opening_line.trim();
+        let content_type = trimmed[delimiter.len()..].trim();
+        let content_type = if content_type.is_empty() {
+            None
+        } else {
+            Some(content_type)
+        };
+        // Content is de-indented relative to the opening delimiter.
+        let indent = opening_line.len() - opening_line.trim_start().len();
+        let opening_span = line_span(self.line_number, opening_line);
+        let escaped_delimiter: String = delimiter.chars().flat_map(|c| ['\\', c]).collect();
+        let mut content_lines = vec![];
+        let end = loop {
+            let line = match self.lines.next() {
+                Some((index, line)) => {
+                    self.line_number = index + 1;
+                    line
+                }
+                None => return (GherkinLine::UnterminatedDocString(trimmed), opening_span),
+            };
+            if line.trim() == delimiter {
+                break line_span(self.line_number, line).end;
+            }
+            let mut unindented = line;
+            for _ in 0..indent {
+                match unindented.strip_prefix(char::is_whitespace) {
+                    Some(rest) => unindented = rest,
+                    None => break,
+                }
+            }
+            content_lines.push(unindented.replace(&escaped_delimiter, delimiter));
+        };
+        let span = Span::new(opening_span.start, end);
+        let doc_string = DocString {
+            content_type: content_type.map(Cow::Borrowed),
+            delimiter: delimiter.into(),
+            content: Cow::Owned(content_lines.join("\n")),
+            span,
+        };
+        (GherkinLine::DocString(doc_string), span)
+    }
+}
+
+/// Finds the language named by the `# language:` header among any comments
+/// before the first keyword of `lines`, along with the header's line number.
+pub(crate) fn detect_language<'a>(
+    lines: &[&'a str],
+) -> ParseResult<(&'a str, Option<usize>, &'static Dialect)> {
+    let header = lines
+        .iter()
+        .enumerate()
+        .take_while(|(_, l)| l.trim().is_empty() || l.trim().starts_with('#'))
+        .find_map(|(index, l)| Some((line_span(index + 1, l), language_header(l)?)));
+    match header {
+        None => Ok((DEFAULT_LANGUAGE, None, Dialect::english())),
+        Some((span, language)) => match Dialect::for_language(language) {
+            Some(dialect) => Ok((language, Some(span.start.line), dialect)),
+            None => Err(ParseError::UnsupportedLanguage {
+                language: language.to_owned(),
+                span,
+            }),
+        },
+    }
+}
+
+/// Locates the trimmed content of `raw_line`, which is line `line_number` of the input.
+fn line_span(line_number: usize, raw_line: &str) -> Span {
+    let indent = raw_line.chars().take_while(|c| c.is_whitespace()).count();
+    let length = raw_line.trim().chars().count();
+    Span::new(
+        Location::new(line_number, indent + 1),
+        Location::new(line_number, indent + length + 1),
+    )
+}
+
+impl<'a> Iterator for GherkinLines<'a> {
+    type Item = Token<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(token) = self.pending.take() {
+            return Some(token);
+        }
+        let (mut tags, mut tags_span) = match self.next_line()? {
+            (GherkinLine::Tags(tags), span) => (tags, span),
+            token => return Some(token),
+        };
+        loop {
+            match self.next_line() {
+                Some((GherkinLine::Tags(more_tags), span)) => {
+                    tags.extend(more_tags);
+                    tags_span = tags_span.to(span);
+                }
+                Some((GherkinLine::BeginGroup(kw, keyword, title, _), span)) => {
+                    return Some((GherkinLine::BeginGroup(kw, keyword, title, tags), span));
+                }
+                other_line => {
+                    self.pending = other_line;
+                    return Some((GherkinLine::Tags(tags), tags_span));
+                }
+            }
+        }
+    }
+}
+
+impl<'a> GherkinLines<'a> {
+    /// Reads the next line which isn't blank or a comment.
+    fn next_line(&mut self) -> Option<Token<'a>> {
+        let mut blank_lines = 0;
+        loop {
+            let (index, raw_line) = self.lines.next()?;
+            self.line_number = index + 1;
+            let line = raw_line.trim();
+            if line.is_empty() {
+                blank_lines += 1;
+                continue;
+            }
+            if line.starts_with('#') {
+                if self.language_line == Some(self.line_number) {
+                    continue;
+                }
+                self.comments.push(Comment {
+                    text: line.into(),
+                    span: line_span(self.line_number, raw_line),
+                });
+                continue;
+            }
+            for delimiter in DOC_STRING_DELIMITERS {
+                if line.starts_with(delimiter) {
+                    return Some(self.read_doc_string(raw_line, delimiter));
+                }
+            }
+            let span = line_span(self.line_number, raw_line);
+            return match GherkinLine::from_str(line, span.start, self.dialect) {
+                GherkinLine::FreeText(text, _) => {
+                    Some((GherkinLine::FreeText(text, blank_lines), span))
+                }
+                other_line => Some((other_line, span)),
+            };
+        }
     }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum GroupingKeyword {
     ScenarioOutline,
-    //ScenarioTemplate, // synonym for ScenarioOutline
     Scenario,
-    //Example, // synonym for Scenario
     Background,
     Examples,
-    //Scenarios, // synonym for Examples
     Feature,
-    // Rule, // not supported yet
+    Rule,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
@@ -84,25 +272,3 @@ pub enum StepKeyword {
     But,
     Bullet,
 }
-
-impl StepKeyword {
-    pub fn from_str(input: &str) -> Result<StepKeyword> {
-        use StepKeyword::*;
-        match input {
-        "Given" => Ok(Given),
-        "When" => Ok(When),
-        "Then" => Ok(Then),
-        "And" => Ok(And),
-        "But" => Ok(But),
-        "*" => Ok(Bullet),
-        _ => bail!("Unrecognized Step keyword '{}' (expected to find 'Given', 'When', 'And', 'Then', 'But' or '*')", input),
-    }
-    }
-}
-
-#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
-pub enum FeatureItemKeyword {
-    Scenario,
-    ScenarioOutline,
-    Background,
-}
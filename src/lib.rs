@@ -0,0 +1,64 @@
+//! Reads gherkin feature files, and exports them as test code.
+//!
+//! ```
+//! use gherkin_reader::{Export, Feature};
+//!
+//! let feature = Feature::from_str(
+//!     "Feature: Farm activities
+//!
+//!     Scenario: Shave a yak
+//!         Given I have a yak",
+//! )?;
+//! assert_eq!(feature.items.len(), 1);
+//! # #[cfg(feature = "nunit")]
+//! # {
+//! let code = feature.export(gherkin_reader::NUnit::default());
+//! assert!(code.contains("public void ShaveAYak()"));
+//! # }
+//! # Ok::<(), gherkin_reader::ParseError>(())
+//! ```
+//!
+//! The AST borrows from the text it was parsed from, until [`Feature::into_owned`]
+//! copies it, and serializes with serde.
+//! It deserializes from any JSON too, owning whatever text it can't borrow.
+//! Tools which edit feature files can use the lossless [`SyntaxTree`] instead,
+//! which keeps every byte of the file. Tools which only read or change the
+//! nodes of the AST can implement [`Visit`] or [`VisitMut`] to traverse it.
+
+pub mod comment;
+pub mod cst;
+mod dialect;
+pub mod error;
+pub mod export;
+pub mod feature;
+mod gherkin_tags;
+pub mod location;
+pub mod pickle;
+pub mod step;
+pub mod tags;
+pub mod visit;
+
+pub use comment::Comment;
+pub use cst::SyntaxTree;
+pub use error::{ParseError, ParseResult};
+pub use export::Export;
+#[cfg(feature = "gherkin")]
+pub use export::Gherkin;
+#[cfg(feature = "json")]
+pub use export::Json;
+#[cfg(feature = "nunit")]
+pub use export::NUnit;
+#[cfg(feature = "messages")]
+pub use export::{GherkinDocument, Messages};
+pub use feature::{
+    ExampleBlock, ExampleRow, Feature, FeatureItem, Rule, Scenario, ScenarioOutline,
+};
+pub use gherkin_tags::StepKeyword;
+pub use location::{Location, Span};
+pub use pickle::{compile, Pickle};
+pub use step::{DataTable, DocString, Step};
+pub use tags::Tag;
+pub use visit::{Visit, VisitMut};
+
+#[cfg(test)]
+mod tests;
@@ -1,26 +1,22 @@
-use crate::export::Export;
 use anyhow::{Context, Result};
 use clap::{crate_version, AppSettings, Clap};
-use feature::Feature;
+use gherkin_reader::{
+    compile, Export, Feature, Gherkin, GherkinDocument, Json, Messages, NUnit, ParseError,
+};
 use glob::glob;
-use std::{fs, io::Write, path::PathBuf};
-
-use crate::export::NUnit;
-
-mod export;
-mod feature;
-mod gherkin_tags;
-mod step;
-mod tags;
-
-#[cfg(test)]
-mod tests;
+use std::{fs, io::Write, path::PathBuf, process};
 
 #[derive(Debug, Clap)]
 enum ExportFormat {
     #[clap(name = "nunit")]
     NUnit,
-    JSON,
+    Json,
+    /// The scenarios a test runner would execute, as JSON
+    Pickles,
+    /// Cucumber Messages, as newline-delimited JSON
+    Messages,
+    /// The AST in the JSON shape of the reference Cucumber parsers
+    Ast,
 }
 
 #[derive(Debug, Clap)]
@@ -39,16 +35,49 @@ enum ErrorBehavior {
     Stderr,
 }
 
+#[derive(Debug, Clap)]
+enum Command {
+    /// Rewrites feature files in place, in a canonical layout
+    Fmt(FmtArguments),
+    /// Writes features read from JSON, in the shape of the `json` format, as feature files
+    ToGherkin(ToGherkinArguments),
+}
+
+#[derive(Debug, Clap)]
+struct FmtArguments {
+    /// Input path (use wildcards for directory contents)
+    input_pattern: String,
+
+    /// Lists the files which aren't formatted rather than rewriting
+    /// them, exiting with an error if there are any
+    #[clap(long)]
+    check: bool,
+}
+
+#[derive(Debug, Clap)]
+struct ToGherkinArguments {
+    /// Input path of the JSON files (use wildcards for directory contents)
+    input_pattern: String,
+
+    /// Destination for the feature files
+    #[clap(parse(from_os_str), default_value(".\\gherkin_output"))]
+    output_path: PathBuf,
+}
+
 #[derive(Debug, Clap)]
 #[clap(
     about="A tool to convert gherkin feature files",
     version=crate_version!(),
-    setting(AppSettings::ArgRequiredElseHelp)
+    setting(AppSettings::ArgRequiredElseHelp),
+    setting(AppSettings::SubcommandsNegateReqs)
 )]
 struct Arguments {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// Input path (use wildcards for directory contents)
     #[clap(parse(from_str))]
-    input_pattern: String,
+    input_pattern: Option<String>,
 
     /// Destination for output source files and logs.
     #[clap(parse(from_os_str), default_value(".\\gherkin_output"))]
@@ -66,22 +95,184 @@ struct Arguments {
     #[clap(arg_enum)]
     #[clap(default_value("log"))]
     error_behavior: ErrorBehavior,
+
+    /// Copy comments from feature files into the generated code
+    #[clap(long)]
+    comments: bool,
+
+    /// Call the background steps at the start of each test, rather than
+    /// from a [SetUp] method
+    #[clap(long)]
+    inline_background: bool,
+
+    /// Drop the accents from letters in generated identifiers
+    #[clap(long)]
+    strip_diacritics: bool,
+
+    /// Keep the bodies of the methods in existing NUnit output files,
+    /// regenerating the code around them
+    #[clap(long)]
+    merge: bool,
 }
 
 fn main() {
     let args = Arguments::parse();
-    let outcome = main_inner(args).context("Fatal error");
-    if let Err(e) = outcome {
-        eprintln!("{:#}", e);
+    let outcome = match args.command {
+        Some(Command::Fmt(fmt_args)) => fmt(fmt_args),
+        Some(Command::ToGherkin(to_gherkin_args)) => to_gherkin(to_gherkin_args),
+        None => main_inner(args).map(|_| true),
+    };
+    match outcome.context("Fatal error") {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("{:#}", e);
+            process::exit(2)
+        }
     }
 }
 
+/// Formats every file matching the input pattern, or with `--check`, lists those
+/// which aren't formatted. Returns whether every file is formatted afterwards.
+fn fmt(args: FmtArguments) -> Result<bool> {
+    let mut all_formatted = true;
+    let paths = glob(&args.input_pattern).context(format!(
+        "Error evaluating paths for input pattern {}",
+        args.input_pattern
+    ))?;
+    for path in paths {
+        let path = path?;
+        if path.is_dir() {
+            continue;
+        }
+        let display_path = path.to_str().unwrap_or("[[Non UTF-8 path]]");
+        let content = fs::read_to_string(&path).context(format!(
+            "Could not read the following input file: {}",
+            display_path
+        ))?;
+        // The BOM and line endings are kept as they are, rather than formatted.
+        let bom = if content.starts_with('\u{FEFF}') {
+            "\u{FEFF}"
+        } else {
+            ""
+        };
+        let crlf = content.contains("\r\n");
+        let source = content.trim_start_matches('\u{FEFF}').replace("\r\n", "\n");
+        let (feature, errors) = Feature::from_str_recovering(&source);
+        let feature = match (feature, errors.is_empty()) {
+            (Some(feature), true) => feature,
+            _ => {
+                eprintln!("{}", describe_errors(display_path, &errors));
+                all_formatted = false;
+                continue;
+            }
+        };
+        let formatted = feature.export(Gherkin);
+        if formatted == source {
+            continue;
+        }
+        if !Gherkin::preserves(&source, &formatted) {
+            eprintln!(
+                "{}: can't be formatted without moving or dropping a line, \
+                such as a comment partway through a description",
+                display_path
+            );
+            all_formatted = false;
+        } else if args.check {
+            println!("{}", display_path);
+            all_formatted = false;
+        } else {
+            let formatted = if crlf {
+                formatted.replace('\n', "\r\n")
+            } else {
+                formatted
+            };
+            fs::write(&path, bom.to_owned() + &formatted).context(format!(
+                "Could not rewrite the following file: {}",
+                display_path
+            ))?;
+            println!("Formatted {}", display_path);
+        }
+    }
+    Ok(all_formatted)
+}
+
+/// Converts every JSON file matching the input pattern into a feature file.
+/// Returns whether every file was converted.
+fn to_gherkin(args: ToGherkinArguments) -> Result<bool> {
+    let mut all_converted = true;
+    fs::create_dir_all(&args.output_path).context(format!(
+        "Could not create output directory: {:?}",
+        &args.output_path
+    ))?;
+    let paths = glob(&args.input_pattern).context(format!(
+        "Error evaluating paths for input pattern {}",
+        args.input_pattern
+    ))?;
+    for path in paths {
+        let path = path?;
+        if path.is_dir() {
+            continue;
+        }
+        let display_path = path.to_str().unwrap_or("[[Non UTF-8 path]]");
+        let content = fs::read_to_string(&path).context(format!(
+            "Could not read the following input file: {}",
+            display_path
+        ))?;
+        let feature: Feature = match serde_json::from_str(content.trim_start_matches('\u{FEFF}')) {
+            Ok(feature) => feature,
+            Err(e) => {
+                eprintln!("{}:{}:{}: {}", display_path, e.line(), e.column(), e);
+                all_converted = false;
+                continue;
+            }
+        };
+        // `x.feature.json`, as written by the `json` format, becomes `x.feature` again.
+        let stem = path
+            .file_stem()
+            .context("Input file not found")?
+            .to_str()
+            .context("File path contains invalid utf-8")?;
+        let name = if stem.ends_with(".feature") {
+            stem.to_owned()
+        } else {
+            stem.to_owned() + ".feature"
+        };
+        fs::write(args.output_path.join(&name), feature.export(Gherkin)).context(format!(
+            "Could not write the following output file: {}",
+            name
+        ))?;
+        println!("Converted {}", display_path);
+    }
+    Ok(all_converted)
+}
+
+/// Lists `errors`, one per line, each with where it was found.
+fn describe_errors(display_path: &str, errors: &[ParseError]) -> String {
+    errors
+        .iter()
+        .map(|error| {
+            let display_location = match error.span() {
+                Some(span) => format!("{}:{}:{}", display_path, span.start.line, span.start.column),
+                None => display_path.to_owned(),
+            };
+            format!("{}: error[{}]: {}", display_location, error.code(), error)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 fn main_inner(args: Arguments) -> Result<()> {
     let mut success_count = 0;
     let mut failure_count = 0;
-    let input_path = args.input_pattern;
+    let input_path = args.input_pattern.context("An input pattern is required")?;
     let export_format = args.export_format;
     let output_dir = args.output_path;
+    let nunit = NUnit {
+        comments: args.comments,
+        inline_background: args.inline_background,
+        strip_diacritics: args.strip_diacritics,
+    };
     fs::create_dir_all(&output_dir).context(format!(
         "Could not create output directory: {:?}",
         &output_dir
@@ -109,29 +300,50 @@ fn main_inner(args: Arguments) -> Result<()> {
             // Trim utf-8 BOM, if present
             let content = content.trim_start_matches("\u{FEFF}");
 
-            let feature = Feature::from_str(content);
-            if let Ok(feature) = feature {
+            // Report every error in the file at once, rather than one per run.
+            let (feature, errors) = Feature::from_str_recovering(content);
+            if let (Some(feature), true) = (feature, errors.is_empty()) {
                 let extension = match export_format {
                     ExportFormat::NUnit => ".cs",
-                    ExportFormat::JSON => ".json",
+                    ExportFormat::Json => ".json",
+                    ExportFormat::Pickles => ".pickles.json",
+                    ExportFormat::Messages => ".ndjson",
+                    ExportFormat::Ast => ".ast.ndjson",
+                };
+                let output_path = output_dir.join((*name).to_owned() + extension);
+                let existing = match export_format {
+                    ExportFormat::NUnit if args.merge => fs::read_to_string(&output_path).ok(),
+                    _ => None,
                 };
                 let mut w = fs::OpenOptions::new()
                     .create(true)
                     .write(true)
-                    .open(output_dir.join((*name).to_owned() + extension))
+                    .truncate(true)
+                    .open(&output_path)
                     .context(format!("Failed to create output file for {}", name))?;
 
                 let content = match export_format {
-                    ExportFormat::NUnit => feature.export(NUnit),
-                    ExportFormat::JSON => serde_json::to_string_pretty(&feature)?,
+                    ExportFormat::NUnit => match &existing {
+                        Some(existing) => NUnit::merge(existing, &feature.export(nunit)),
+                        None => feature.export(nunit),
+                    },
+                    ExportFormat::Json => feature.export(Json),
+                    ExportFormat::Pickles => serde_json::to_string_pretty(&compile(&feature))?,
+                    ExportFormat::Messages => feature.export(Messages {
+                        uri: path.to_str().unwrap_or(name),
+                        source: content,
+                    }),
+                    ExportFormat::Ast => feature.export(GherkinDocument {
+                        uri: path.to_str().unwrap_or(name),
+                        source: content,
+                    }),
                 };
                 //w.write(content.as_bytes())?;
                 write!(w, "{}", content)?;
                 success_count += 1;
-            } else if let Err(error) = feature {
+            } else {
                 let display_path = path.to_str().unwrap_or("[[Non UTF-8 path]]");
-                let display_error = format!("{:#}", error).replace(':', ":\n");
-                let error_text = format!("Error parsing {}: {}", display_path, display_error);
+                let error_text = describe_errors(display_path, &errors);
                 match args.error_behavior {
                     ErrorBehavior::Log => {
                         fs::write(output_dir.join((*name).to_owned() + ".log"), error_text)
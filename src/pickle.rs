@@ -0,0 +1,346 @@
+//! Compiling features into pickles: the flat list of scenarios a test runner executes.
+//!
+//! Each scenario becomes one pickle, with the steps of any backgrounds before
+//! its own. Each row of a scenario outline's examples becomes one pickle, with
+//! the row's values substituted for the `<placeholder>`s in its steps.
+//!
+//! IDs are derived from where nodes are in the feature file, so compiling
+//! the same file twice gives the same IDs. Nodes without a location, such as
+//! those of a feature deserialized without spans, are identified by the path
+//! to them within the feature instead.
+
+use crate::feature::{ExampleBlock, ExampleRow, Feature, FeatureItem, Scenario, ScenarioOutline};
+use crate::gherkin_tags::StepKeyword;
+use crate::location::Span;
+use crate::step::Step;
+use crate::tags::Tag;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// A single concrete scenario, ready to execute.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pickle<'a> {
+    pub id: String,
+    pub name: String,
+    pub language: Cow<'a, str>,
+    pub steps: Vec<PickleStep>,
+    /// The effective tags of the scenario, or of the examples for an outline.
+    pub tags: Vec<Tag<'a>>,
+    /// The IDs of the scenario, and of the example row it was expanded from.
+    pub ast_node_ids: Vec<String>,
+    /// Where the scenario, or example row, was read from.
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PickleStep {
+    pub id: String,
+    pub keyword: StepKeyword,
+    pub step_type: PickleStepType,
+    pub text: String,
+    pub argument: Option<PickleArgument>,
+    /// The IDs of the step, and of the example row it was expanded from.
+    pub ast_node_ids: Vec<String>,
+}
+
+/// What a step does, with `And`, `But` and `*` taking on the type of the step before them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PickleStepType {
+    Context,
+    Action,
+    Outcome,
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PickleArgument {
+    DocString {
+        content_type: Option<String>,
+        content: String,
+    },
+    DataTable(Vec<Vec<String>>),
+}
+
+/// The ID of the node read from `span`.
+pub(crate) fn node_id(span: Span) -> String {
+    format!("{}:{}", span.start.line, span.start.column)
+}
+
+/// The ID of the node read from `span`, or for a node without a location,
+/// the path to it within the feature, such as `r1.i0.s2` for the third step
+/// of the first scenario of the second rule.
+fn path_id(span: Span, path: &str) -> String {
+    // Lines count from 1, so a node on line 0 wasn't read from a file.
+    if span.start.line != 0 {
+        node_id(span)
+    } else {
+        format!("#{}", path)
+    }
+}
+
+/// The ID of a pickle compiled from the node, or the outline and row, identified by
+/// `node_id`. Pickles have IDs of their own, as a plain scenario's ID would
+/// otherwise be its pickle's too, and IDs must be unique across a message stream.
+fn pickle_id(node_id: &str) -> String {
+    format!("pickle:{}", node_id)
+}
+
+/// A background which applies to the scenarios being compiled, with its path.
+type Background<'b, 'a> = (&'b Scenario<'a>, String);
+
+/// Expands `feature` into the pickles a test runner would execute, in file order.
+pub fn compile<'a>(feature: &Feature<'a>) -> Vec<Pickle<'a>> {
+    let mut pickles = vec![];
+    let backgrounds: Vec<Background> = feature
+        .background
+        .iter()
+        .map(|background| (background, String::from("b")))
+        .collect();
+    for (i, item) in feature.items.iter().enumerate() {
+        let path = format!("i{}", i);
+        compile_item(feature, item, &path, &backgrounds, &mut pickles);
+    }
+    for (r, rule) in feature.rules.iter().enumerate() {
+        let mut backgrounds = backgrounds.clone();
+        backgrounds.extend(
+            rule.background
+                .iter()
+                .map(|background| (background, format!("r{}.b", r))),
+        );
+        for (i, item) in rule.items.iter().enumerate() {
+            let path = format!("r{}.i{}", r, i);
+            compile_item(feature, item, &path, &backgrounds, &mut pickles);
+        }
+    }
+    pickles
+}
+
+fn compile_item<'a>(
+    feature: &Feature<'a>,
+    item: &FeatureItem<'a>,
+    path: &str,
+    backgrounds: &[Background<'_, 'a>],
+    pickles: &mut Vec<Pickle<'a>>,
+) {
+    match item {
+        FeatureItem::Bare(scenario) => {
+            pickles.push(compile_scenario(feature, scenario, path, backgrounds))
+        }
+        FeatureItem::Outline(outline) => {
+            for (b, block) in outline.example_blocks.iter().enumerate() {
+                for (r, row) in block.examples.iter().enumerate() {
+                    let row_path = format!("{}.e{}.{}", path, b, r);
+                    pickles.push(compile_example(
+                        feature,
+                        outline,
+                        path,
+                        block,
+                        row,
+                        &row_path,
+                        backgrounds,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn compile_scenario<'a>(
+    feature: &Feature<'a>,
+    scenario: &Scenario<'a>,
+    path: &str,
+    backgrounds: &[Background<'_, 'a>],
+) -> Pickle<'a> {
+    let scenario_id = path_id(scenario.span, path);
+    let id = pickle_id(&scenario_id);
+    let substitutions = Substitutions::none();
+    let mut steps = PickleSteps::new(&id);
+    // A scenario without steps doesn't run its backgrounds either.
+    if !scenario.steps.is_empty() {
+        for (background, background_path) in backgrounds {
+            steps.extend(&background.steps, background_path, &substitutions, None);
+        }
+    }
+    steps.extend(&scenario.steps, path, &substitutions, None);
+    Pickle {
+        name: scenario.name.to_string(),
+        language: feature.language.clone(),
+        steps: steps.steps,
+        tags: scenario.effective_tags.clone(),
+        ast_node_ids: vec![scenario_id],
+        span: scenario.span,
+        id,
+    }
+}
+
+fn compile_example<'a>(
+    feature: &Feature<'a>,
+    outline: &ScenarioOutline<'a>,
+    path: &str,
+    block: &ExampleBlock<'a>,
+    row: &ExampleRow<'a>,
+    row_path: &str,
+    backgrounds: &[Background<'_, 'a>],
+) -> Pickle<'a> {
+    let outline_id = path_id(outline.span, path);
+    let row_id = path_id(row.span, row_path);
+    let id = pickle_id(&format!("{};{}", outline_id, row_id));
+    let substitutions = Substitutions::new(&block.labels, row);
+    let mut steps = PickleSteps::new(&id);
+    if !outline.steps.is_empty() {
+        for (background, background_path) in backgrounds {
+            steps.extend(
+                &background.steps,
+                background_path,
+                &Substitutions::none(),
+                None,
+            );
+        }
+    }
+    steps.extend(&outline.steps, path, &substitutions, Some(&row_id));
+    Pickle {
+        name: substitutions.apply(&outline.name),
+        language: feature.language.clone(),
+        steps: steps.steps,
+        tags: block.effective_tags.clone(),
+        ast_node_ids: vec![outline_id, row_id],
+        span: row.span,
+        id,
+    }
+}
+
+/// The values of an example row, to put in place of the placeholders named by its labels.
+struct Substitutions<'r> {
+    values: Vec<(&'r str, &'r str)>,
+}
+
+impl<'r> Substitutions<'r> {
+    fn new(labels: &'r ExampleRow, row: &'r ExampleRow) -> Self {
+        let values = labels
+            .entries
+            .iter()
+            .zip(&row.entries)
+            .map(|(label, value)| (label.as_ref(), value.as_ref()))
+            .collect();
+        Substitutions { values }
+    }
+
+    fn none() -> Self {
+        Substitutions { values: vec![] }
+    }
+
+    fn value(&self, label: &str) -> Option<&'r str> {
+        self.values
+            .iter()
+            .find(|(l, _)| *l == label)
+            .map(|&(_, value)| value)
+    }
+
+    /// Replaces every `<label>` in `text`, in a single pass so that
+    /// placeholders within the values themselves are left as they are.
+    fn apply(&self, text: &str) -> String {
+        let mut applied = String::with_capacity(text.len());
+        let mut remaining = text;
+        while let Some(open) = remaining.find('<') {
+            applied += &remaining[..open];
+            let after_open = &remaining[open + 1..];
+            let substitution = after_open
+                .split_once('>')
+                .and_then(|(label, after_close)| Some((self.value(label)?, after_close)));
+            match substitution {
+                Some((value, after_close)) => {
+                    applied += value;
+                    remaining = after_close;
+                }
+                None => {
+                    applied.push('<');
+                    remaining = after_open;
+                }
+            }
+        }
+        applied + remaining
+    }
+
+    /// Rebuilds the text of `step`, with values for the placeholders it has labels for.
+    fn step_text(&self, step: &Step) -> String {
+        let mut text = String::new();
+        for (i, literal) in step.literals.iter().enumerate() {
+            text += literal;
+            if let Some(variable) = step.variables.get(i) {
+                match self.value(variable) {
+                    Some(value) => text += value,
+                    None => text += &format!("<{}>", variable),
+                }
+            }
+        }
+        text
+    }
+}
+
+struct PickleSteps<'p> {
+    pickle_id: &'p str,
+    steps: Vec<PickleStep>,
+}
+
+impl<'p> PickleSteps<'p> {
+    fn new(pickle_id: &'p str) -> Self {
+        PickleSteps {
+            pickle_id,
+            steps: vec![],
+        }
+    }
+
+    /// Adds `steps`, those of the node at `path`.
+    fn extend(
+        &mut self,
+        steps: &[Step],
+        path: &str,
+        substitutions: &Substitutions,
+        row_id: Option<&str>,
+    ) {
+        let mut step_type = PickleStepType::Unknown;
+        for (s, step) in steps.iter().enumerate() {
+            step_type = match step.keyword {
+                StepKeyword::Given => PickleStepType::Context,
+                StepKeyword::When => PickleStepType::Action,
+                StepKeyword::Then => PickleStepType::Outcome,
+                StepKeyword::And | StepKeyword::But | StepKeyword::Bullet => step_type,
+            };
+            let step_id = path_id(step.span, &format!("{}.s{}", path, s));
+            let argument = if let Some(doc_string) = &step.doc_string {
+                Some(PickleArgument::DocString {
+                    content_type: doc_string
+                        .content_type
+                        .as_deref()
+                        .map(|content_type| substitutions.apply(content_type)),
+                    content: substitutions.apply(&doc_string.content),
+                })
+            } else {
+                step.data_table.as_ref().map(|table| {
+                    PickleArgument::DataTable(
+                        table
+                            .rows
+                            .iter()
+                            .map(|row| {
+                                row.entries
+                                    .iter()
+                                    .map(|cell| substitutions.apply(cell))
+                                    .collect()
+                            })
+                            .collect(),
+                    )
+                })
+            };
+            self.steps.push(PickleStep {
+                id: format!("{}/{}", self.pickle_id, step_id),
+                keyword: step.keyword,
+                step_type,
+                text: substitutions.step_text(step),
+                argument,
+                ast_node_ids: std::iter::once(step_id)
+                    .chain(row_id.map(str::to_owned))
+                    .collect(),
+            });
+        }
+    }
+}
@@ -1,21 +1,87 @@
 use crate::{
-    feature::ParseStr,
-    gherkin_tags::{FeatureItemKeyword, StepKeyword},
+    comment::Comment,
+    error::{ParseError, ParseResult},
+    feature::{owned, owned_all, CommentSlot, ExampleRow, ParseOutcome},
+    gherkin_tags::{GherkinLine, StepKeyword, Token},
+    location::Span,
 };
-use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 
+/// A block of text delimited by `"""` or ```` ``` ````, passed as an argument to a step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocString<'a> {
+    /// The text following the opening delimiter, such as `json` in `"""json`
+    #[serde(default)]
+    pub content_type: Option<Cow<'a, str>>,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: Cow<'a, str>,
+    /// The contents, de-indented to match the opening delimiter,
+    /// with escaped delimiters replaced.
+    pub content: Cow<'a, str>,
+    #[serde(default)]
+    pub span: Span,
+}
+
+impl<'a> DocString<'a> {
+    /// Copies any text borrowed from the input.
+    pub fn into_owned(self) -> DocString<'static> {
+        DocString {
+            content_type: self.content_type.map(owned),
+            delimiter: owned(self.delimiter),
+            content: owned(self.content),
+            span: self.span,
+        }
+    }
+}
+
+fn default_delimiter<'a>() -> Cow<'a, str> {
+    Cow::Borrowed("\"\"\"")
+}
+
+/// A table of cells passed as an argument to a step.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DataTable<'a> {
+    pub rows: Vec<ExampleRow<'a>>,
+}
+
+impl<'a> DataTable<'a> {
+    /// Copies any text borrowed from the input.
+    pub fn into_owned(self) -> DataTable<'static> {
+        DataTable {
+            rows: self.rows.into_iter().map(ExampleRow::into_owned).collect(),
+        }
+    }
+}
+
+/// A step, such as `Given I have a <animal>`.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Step<'a> {
-    pub(crate) keyword: StepKeyword,
-    #[serde(borrow)]
-    pub(crate) literals: Vec<&'a str>,
-    #[serde(borrow)]
-    pub(crate) variables: Vec<&'a str>,
+    pub keyword: StepKeyword,
+    /// The keyword as written, such as `Angenommen ` for `Given` in German.
+    pub keyword_text: Cow<'a, str>,
+    /// The step text either side of its `<placeholder>`s, so that
+    /// `literals[i]` comes just before `variables[i]`.
+    pub literals: Vec<Cow<'a, str>>,
+    #[serde(default)]
+    pub variables: Vec<Cow<'a, str>>,
+    #[serde(default)]
+    pub doc_string: Option<DocString<'a>>,
+    #[serde(default)]
+    pub data_table: Option<DataTable<'a>>,
+    #[serde(default)]
+    pub comments: Vec<Comment<'a>>,
+    #[serde(default)]
+    pub span: Span,
 }
 
 impl<'a> Step<'a> {
-    pub fn new(keyword: StepKeyword, input: &'a str) -> Result<Step<'a>> {
+    pub fn new(
+        keyword: StepKeyword,
+        keyword_text: &'a str,
+        input: &'a str,
+        span: Span,
+    ) -> ParseResult<Step<'a>> {
         let mut remaining_text = input.trim();
         let mut literals = vec![];
         let mut variables = vec![];
@@ -23,15 +89,12 @@ impl<'a> Step<'a> {
             if let Some((literal, text)) = remaining_text.split_once('<') {
                 remaining_text = text;
                 literals.push(literal);
-                let (variable, text) = remaining_text.split_once('>').with_context(|| {
-                    format!(
-                        "The following step: \n\
-                        `{step}`\n\
-                        ends with an unterminated variable expression{}\n\
-                        `{expression}`",
-                        step = input,
-                        expression = remaining_text
-                    )
+                let (variable, text) = remaining_text.split_once('>').ok_or_else(|| {
+                    ParseError::UnterminatedPlaceholder {
+                        span,
+                        step: input.to_owned(),
+                        expression: remaining_text.to_owned(),
+                    }
                 })?;
                 remaining_text = text;
                 variables.push(variable);
@@ -42,55 +105,95 @@ impl<'a> Step<'a> {
         }
         Ok(Step {
             keyword,
-            literals,
-            variables,
+            keyword_text: keyword_text.into(),
+            literals: literals.into_iter().map(Cow::Borrowed).collect(),
+            variables: variables.into_iter().map(Cow::Borrowed).collect(),
+            doc_string: None,
+            data_table: None,
+            comments: vec![],
+            span,
         })
     }
-}
 
-impl<'a> ParseStr<'a> for FeatureItemKeyword {
-    fn from_str(input: &'a str) -> Result<Self>
-    where
-        Self: Sized,
-    {
-        use FeatureItemKeyword::*;
-        match input {
-            "Background" => Ok(Background),
-            "Scenario" | "Example" => Ok(Scenario),
-            "Scenario Outline" | "Scenario Template" => Ok(ScenarioOutline),
-            _ => bail!(
-                "Keyword {} was expected to begin a Scenario \
-                or Scenario Outline (was not any of 'Scenario', \
-                'Scenario Outline', 'Scenario Template', or 'Example')"
-            ),
+    /// Copies any text borrowed from the input.
+    pub fn into_owned(self) -> Step<'static> {
+        Step {
+            keyword: self.keyword,
+            keyword_text: owned(self.keyword_text),
+            literals: owned_all(self.literals),
+            variables: owned_all(self.variables),
+            doc_string: self.doc_string.map(DocString::into_owned),
+            data_table: self.data_table.map(DataTable::into_owned),
+            comments: self.comments.into_iter().map(Comment::into_owned).collect(),
+            span: self.span,
         }
     }
-}
 
-#[derive(PartialEq, Eq, Debug)]
-pub enum Keyword {
-    Feature,
-    FeatureItem(FeatureItemKeyword),
-    Examples,
-    Step(StepKeyword),
-}
+    /// Adds the places comments can be attached to within this step:
+    /// the step itself, and each row of its data table.
+    pub(crate) fn comment_slots<'b>(&'b mut self, slots: &mut Vec<CommentSlot<'b, 'a>>) {
+        slots.push((self.span.start, &mut self.comments));
+        for row in self.data_table.iter_mut().flat_map(|table| &mut table.rows) {
+            slots.push((row.span.start, &mut row.comments));
+        }
+    }
 
-impl<'a> ParseStr<'a> for Keyword {
-    fn from_str(input: &str) -> Result<Self>
-    where
-        Self: Sized,
-    {
-        use Keyword::*;
-        if let Ok(fik) = FeatureItemKeyword::from_str(input) {
-            Ok(FeatureItem(fik))
-        } else if let Ok(step) = StepKeyword::from_str(input) {
-            Ok(Step(step))
-        } else {
-            match input {
-                "Feature" => Ok(Feature),
-                "Examples" | "Scenarios" => Ok(Examples),
-                _ => bail!("Coult not parse input {} as any known keyword.", input),
+    /// The step text as written, with its `<placeholder>`s in place.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for (i, literal) in self.literals.iter().enumerate() {
+            text += literal;
+            if let Some(variable) = self.variables.get(i) {
+                text += &format!("<{}>", variable);
             }
         }
+        text
+    }
+
+    /// Reads a step, along with the argument which may follow it.
+    pub(crate) fn from_lines(
+        keyword: StepKeyword,
+        keyword_text: &'a str,
+        input: &'a str,
+        span: Span,
+        mut lines: impl Iterator<Item = Token<'a>>,
+    ) -> ParseResult<ParseOutcome<'a, Self>> {
+        let mut step = Step::new(keyword, keyword_text, input, span)?;
+        let terminating_line = match lines.next() {
+            Some((GherkinLine::DocString(doc_string), span)) => {
+                step.span = step.span.to(span);
+                step.doc_string = Some(doc_string);
+                lines.next()
+            }
+            Some((GherkinLine::UnterminatedDocString(opening_line), span)) => {
+                return Err(ParseError::UnterminatedDocString {
+                    span,
+                    delimiter: opening_line.to_owned(),
+                });
+            }
+            Some((GherkinLine::ExampleEntry(row), span)) => {
+                let mut rows: Vec<ExampleRow> = vec![];
+                let mut line = Some((GherkinLine::ExampleEntry(row), span));
+                while let Some((GherkinLine::ExampleEntry(row), span)) = line {
+                    let table_row = ExampleRow::from_str(row, span)?;
+                    if let Some(first_row) = rows.first() {
+                        if first_row.entries.len() != table_row.entries.len() {
+                            return Err(ParseError::InconsistentTableWidth {
+                                span,
+                                expected: first_row.entries.len(),
+                                found: table_row.entries.len(),
+                            });
+                        }
+                    }
+                    step.span = step.span.to(span);
+                    rows.push(table_row);
+                    line = lines.next();
+                }
+                step.data_table = Some(DataTable { rows });
+                line
+            }
+            other_line => other_line,
+        };
+        Ok((step, terminating_line))
     }
 }
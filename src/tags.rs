@@ -1,26 +1,71 @@
-use std::{iter::Map, str::Split};
+use crate::feature::owned;
+use crate::location::{Location, Span};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// A single `@tag`, named without its leading `@`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tag<'a> {
+    pub name: Cow<'a, str>,
+    #[serde(default)]
+    pub span: Span,
+}
+
+impl<'a> Tag<'a> {
+    /// Copies the name if it's borrowed from the input.
+    pub fn into_owned(self) -> Tag<'static> {
+        Tag {
+            name: owned(self.name),
+            span: self.span,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
-pub(crate) struct GherkinTags<'a>(&'a str);
+pub(crate) struct GherkinTags<'a> {
+    after_first_at_sign: &'a str,
+    first_at_sign: Location,
+}
 
 impl<'a> GherkinTags<'a> {
-    pub(crate) fn new(after_first_at_sign: &'a str) -> Self {
-        GherkinTags(after_first_at_sign)
+    pub(crate) fn new(after_first_at_sign: &'a str, first_at_sign: Location) -> Self {
+        GherkinTags {
+            after_first_at_sign,
+            first_at_sign,
+        }
     }
 }
 
 impl<'a> IntoIterator for GherkinTags<'a> {
-    type Item = &'a str;
+    type Item = Tag<'a>;
 
-    type IntoIter = GherkinTagsIterator<'a>;
+    type IntoIter = std::vec::IntoIter<Tag<'a>>;
     fn into_iter(self) -> Self::IntoIter {
-        GherkinTagsIterator(self.0.split('@').map(str::trim))
+        let line = self.first_at_sign.line;
+        let mut at_sign_column = self.first_at_sign.column;
+        let mut tags = vec![];
+        for segment in self.after_first_at_sign.split('@') {
+            let end_column = at_sign_column + 1 + segment.trim_end().chars().count();
+            tags.push(Tag {
+                name: segment.trim().into(),
+                span: Span::new(
+                    Location::new(line, at_sign_column),
+                    Location::new(line, end_column),
+                ),
+            });
+            at_sign_column += segment.chars().count() + 1;
+        }
+        tags.into_iter()
     }
 }
-pub(crate) struct GherkinTagsIterator<'a>(Map<Split<'a, char>, for<'r> fn(&'r str) -> &'r str>);
-impl<'a> Iterator for GherkinTagsIterator<'a> {
-    type Item = &'a str;
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.next()
+
+/// Combines the tags a node inherits with its own, leaving out repeated names.
+pub(crate) fn inherit_tags<'a>(inherited: &[Tag<'a>], own: &[Tag<'a>]) -> Vec<Tag<'a>> {
+    let mut tags = inherited.to_vec();
+    for tag in own {
+        if !tags.iter().any(|existing| existing.name == tag.name) {
+            tags.push(tag.clone());
+        }
     }
+    tags
 }
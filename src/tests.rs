@@ -1,4 +1,14 @@
-use crate::feature::Feature;
+use crate::error::ParseError;
+#[cfg(any(feature = "nunit", feature = "messages", feature = "gherkin"))]
+use crate::export::Export;
+#[cfg(feature = "messages")]
+use crate::export::Messages;
+#[cfg(feature = "nunit")]
+use crate::export::NUnit;
+use crate::feature::{Feature, FeatureItem};
+use crate::gherkin_tags::StepKeyword;
+use crate::location::{Location, Span};
+use crate::pickle::{compile, PickleArgument, PickleStepType};
 use anyhow::Result;
 #[test]
 fn test_load_feature() -> Result<()> {
@@ -29,7 +39,8 @@ fn test_load_feature() -> Result<()> {
         | horse  | neigh |
         | pig    | oink  |
     "###;
-    Feature::from_str(input).map(|_| ())
+    Feature::from_str(input)?;
+    Ok(())
 }
 
 #[test]
@@ -68,5 +79,1734 @@ fn test_load_outline_with_multiple_example_blocks() -> Result<()> {
         | duck    | quack |
         | chicken | cluck |
     "###;
-    Feature::from_str(input).map(|_| ())
+    Feature::from_str(input)?;
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_load_rules() -> Result<()> {
+    let input = r###"
+    Feature: Farm activities
+
+    Scenario: Feed the animals
+        Given I have some feed
+
+    @Livestock
+    Rule: Only shaved animals can be sold
+        Shaving happens in the spring.
+
+    Background:
+        Given I am Old McDonald
+
+    @Yak
+    Scenario: Shave a yak
+        Given I have a yak
+        When I shave the yak
+        Then I can sell the yak
+
+    Rule: Eggs are collected daily
+
+    Scenario Outline: Collect eggs
+        Given I have a <bird>
+        Then I collect <count> eggs
+    Examples:
+        | bird    | count |
+        | chicken | 1     |
+    "###;
+    let feature = Feature::from_str(input)?;
+    assert_eq!(feature.items.len(), 1);
+    assert!(feature.background.is_none());
+    assert_eq!(feature.rules.len(), 2);
+
+    let shaving = &feature.rules[0];
+    assert_eq!(shaving.name, "Only shaved animals can be sold");
+    assert_eq!(shaving.free_text, vec!["Shaving happens in the spring."]);
+    assert_eq!(shaving.tags[0].name, "Livestock");
+    assert!(shaving.background.is_some());
+    assert_eq!(shaving.items.len(), 1);
+    assert_eq!(feature.rules[1].items.len(), 1);
+
+    let output = feature.export(NUnit::default());
+    assert!(output.contains("    public class OnlyShavedAnimalsCanBeSold : FarmActivitiesSteps\n"));
+    assert!(output.contains("        public void ShaveAYak()\n"));
+    Ok(())
+}
+
+#[test]
+fn test_load_doc_strings() -> Result<()> {
+    let input = r###"
+    Feature: Farm activities
+
+    Scenario: Send the yak report
+        Given the yak report is
+            """json
+            {
+              "hair": "shaved" # not a comment
+            }
+            Scenario: not a keyword
+            \"\"\"
+            """
+        And the yak report footer is
+          ```
+            Shaved by Old McDonald
+          ```
+        When I send the yak report
+    "###;
+    let feature = Feature::from_str(input)?;
+    let scenario = match &feature.items[0] {
+        FeatureItem::Bare(scenario) => scenario,
+        FeatureItem::Outline(_) => panic!("Expected a plain scenario"),
+    };
+    assert_eq!(scenario.steps.len(), 3);
+
+    let report = scenario.steps[0].doc_string.as_ref().unwrap();
+    assert_eq!(report.content_type.as_deref(), Some("json"));
+    assert_eq!(report.delimiter, "\"\"\"");
+    assert_eq!(
+        report.content,
+        "{\n  \"hair\": \"shaved\" # not a comment\n}\nScenario: not a keyword\n\"\"\""
+    );
+
+    let footer = scenario.steps[1].doc_string.as_ref().unwrap();
+    assert_eq!(footer.content_type, None);
+    assert_eq!(footer.content, "  Shaved by Old McDonald");
+    assert!(scenario.steps[2].doc_string.is_none());
+    Ok(())
+}
+
+#[test]
+fn test_unterminated_doc_string() {
+    let input = r###"
+    Feature: Farm activities
+
+    Scenario: Send the yak report
+        Given the yak report is
+            """
+            Everything is fine
+    "###;
+    match Feature::from_str(input) {
+        Err(error @ ParseError::UnterminatedDocString { .. }) => {
+            assert_eq!(error.span().unwrap().start, Location::new(6, 13));
+        }
+        other => panic!("Expected an unterminated doc string, got {:?}", other),
+    }
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_load_data_tables() -> Result<()> {
+    let input = r###"
+    Feature: Farm activities
+
+    Scenario Outline: Shave some animals
+        Given I have the following animals:
+            | animal | hair     |
+            | yak    | shaggy   |
+            | sheep  | wool \| fleece |
+        When I shave the <animal>
+        Then I have no more hair
+    Examples:
+        | animal |
+        | yak    |
+    "###;
+    let feature = Feature::from_str(input)?;
+    let outline = match &feature.items[0] {
+        FeatureItem::Outline(outline) => outline,
+        FeatureItem::Bare(_) => panic!("Expected a scenario outline"),
+    };
+    let table = outline.steps[0].data_table.as_ref().unwrap();
+    assert_eq!(table.rows.len(), 3);
+    assert_eq!(table.rows[2].entries, vec!["sheep", "wool | fleece"]);
+    assert!(outline.steps[1].data_table.is_none());
+
+    let output = feature.export(NUnit::default());
+    assert!(output.contains(
+        "        IHaveTheFollowingAnimals(new string[][] { \
+        new string[] { @\"animal\", @\"hair\" }, \
+        new string[] { @\"yak\", @\"shaggy\" }, \
+        new string[] { @\"sheep\", @\"wool | fleece\" } });"
+    ));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_scenario_step_calls() -> Result<()> {
+    let input = r###"
+    Feature: Coffee
+
+    Scenario: Order a coffee
+        Given the menu has "flat white" and "latte"
+        When I order a "flat white"
+        Then I am asked "\"anything else\"?"
+        And my order is
+            | drink      |
+            | flat white |
+    "###;
+    let output = Feature::from_str(input)?.export(NUnit::default());
+    let expected = r###"    [Test]
+    public void OrderACoffee()
+    {
+        TheMenuHas___And___(@"flat white", @"latte");
+        IOrderA___(@"flat white");
+        IAmAsked___(@"""anything else""?");
+        MyOrderIs(new string[][] { new string[] { @"drink" }, new string[] { @"flat white" } });
+    }
+"###;
+    assert!(output.contains(expected), "{}", output);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_quoted_step_values() -> Result<()> {
+    let input = r###"
+    Feature: Farm activities
+
+    Scenario: Label the eggs
+        When I label the eggs "grade \"A\" \\ large" in "C:\Barn"
+    "###;
+    let output = Feature::from_str(input)?.export(NUnit::default());
+    assert!(output.contains(
+        "        ILabelTheEggs___In___(@\"grade \"\"A\"\" \\ large\", @\"C:\\Barn\");\n"
+    ));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_background_set_up() -> Result<()> {
+    let input = r###"
+    Feature: Farm activities
+
+    Background:
+        Given the farm has opened
+            """
+            Open from "9" to 5
+            """
+
+    Scenario: Feed the yak
+        When I feed the yak
+
+    Rule: Selling
+        Background:
+            Given the market is open
+                | stall |
+                | 4     |
+
+        Scenario: Sell the yak
+            Then I sell the yak
+    "###;
+    let feature = Feature::from_str(input)?;
+    let opened = "TheFarmHasOpened(\"Open from \\\"9\\\" to 5\");\n";
+    let market = "TheMarketIsOpen(new string[][] { \
+        new string[] { @\"stall\" }, new string[] { @\"4\" } });\n";
+
+    let output = without_hashes(&feature.export(NUnit::default()));
+    assert!(output.contains(&format!(
+        "    [SetUp]\n    public void Background()\n    {{\n        {}    }}\n    // gherkin: scenario Feed the yak\n    [Test]\n    public void FeedTheYak()\n    {{\n        IFeedTheYak();",
+        opened
+    )));
+    assert!(output.contains(&format!(
+        "        [SetUp]\n        public void Background()\n        {{\n            {}            {}        }}\n",
+        opened, market
+    )));
+
+    let output = feature.export(NUnit {
+        inline_background: true,
+        ..NUnit::default()
+    });
+    assert!(!output.contains("[SetUp]"));
+    assert!(output.contains(&format!(
+        "    public void FeedTheYak()\n    {{\n        {}        IFeedTheYak();",
+        opened
+    )));
+    assert!(output.contains(&format!(
+        "        public void SellTheYak()\n        {{\n            {}            {}            ISellTheYak();",
+        opened, market
+    )));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_step_stubs() -> Result<()> {
+    let input = r###"
+    Feature: Egg collection
+
+    Scenario: Collect from one hen
+        Given I have "1" hen
+        When I collect "eggs" from "the barn"
+        Then I have 1 eggs
+
+    Rule: Flocks
+
+    Scenario Outline: Collect from a flock
+        Given I have <hens> hen
+        When I collect "eggs" from "the barn"
+        Then I have <eggs> eggs
+    Examples:
+        | hens | eggs |
+        | 3    | 3    |
+        | many | 6    |
+    "###;
+    let output = without_hashes(&Feature::from_str(input)?.export(NUnit::default()));
+    assert!(output.contains("public class EggCollection : EggCollectionSteps\n"));
+    assert!(output.contains("    public class Flocks : EggCollectionSteps\n"));
+    assert!(output.contains(
+        "        public void CollectFromAFlock(string hens, long eggs)\n        {\n            \
+        IHave___Hen(hens);\n            \
+        ICollect___From___(@\"eggs\", @\"the barn\");\n            \
+        IHave___Eggs(eggs);\n        }\n"
+    ));
+    let stubs = &output[output
+        .find("public partial class EggCollectionSteps")
+        .unwrap()..];
+    assert_eq!(
+        stubs,
+        "public partial class EggCollectionSteps
+{
+    // gherkin: step IHave___Hen(string)
+    public void IHave___Hen(string text)
+    {
+        throw new NotImplementedException();
+    }
+
+    // gherkin: step ICollect___From___(string, string)
+    public void ICollect___From___(string text, string text_2)
+    {
+        throw new NotImplementedException();
+    }
+
+    // gherkin: step IHave1Eggs()
+    public void IHave1Eggs()
+    {
+        throw new NotImplementedException();
+    }
+
+    // gherkin: step IHave___Eggs(long)
+    public void IHave___Eggs(long eggs)
+    {
+        throw new NotImplementedException();
+    }
+}"
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_identifiers() -> Result<()> {
+    let input = r###"
+    Feature: 3 ducks
+
+    Scenario: Feed the ducks!
+        Given a duck
+
+    Scenario: Feed the ducks?
+        Given a duck.
+
+    Scenario: A duck
+        Given a duck
+
+    Scenario Outline: Event
+        Given I count <class> ducks and <class!> ducks
+    Examples:
+        | class | class! |
+        | 1     | 2      |
+
+    Scenario: Café
+        Given a café
+    "###;
+    let feature = Feature::from_str(input)?;
+    let output = feature.export(NUnit::default());
+    assert!(output.contains("public class _3Ducks : _3DucksSteps\n"));
+    assert!(output.contains("    public void FeedTheDucks()\n    {\n        ADuck();"));
+    assert!(output.contains("    public void FeedTheDucks_2()\n    {\n        ADuck_2();"));
+    assert!(output.contains("    public void ADuck_3()\n    {\n        ADuck();"));
+    assert!(output.contains(
+        "    public void Event(long @class, long class_2)\n    {\n        ICount___DucksAnd___Ducks(@class, class_2);"
+    ));
+    assert!(
+        output.contains("    public void ICount___DucksAnd___Ducks(long @class, long class_2)\n")
+    );
+    assert!(output.contains("    public void Café()\n    {\n        ACafé();"));
+
+    let output = feature.export(NUnit {
+        strip_diacritics: true,
+        ..NUnit::default()
+    });
+    assert!(output.contains("    public void Cafe()\n    {\n        ACafe();"));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_merge_nunit() -> Result<()> {
+    let before = r###"
+    Feature: Farm activities
+
+    Scenario Outline: Feed a <animal>
+        When I feed the <animal>
+    Examples:
+        | animal |
+        | goat   |
+
+    Scenario: Shave the yak
+        Then the yak is shaved
+    "###;
+    let after = r###"
+    Feature: Farm activities
+
+    Scenario Outline: Feed a <animal>
+        When I feed the <animal>
+    Examples:
+        | animal |
+        | goat   |
+        | sheep  |
+
+    Scenario: Milk the goat
+        Then the goat is milked
+    "###;
+    let existing = Feature::from_str(before)?
+        .export(NUnit::default())
+        .replace(
+            "        IFeedThe___(animal);\n",
+            "        IFeedThe___(animal);\n        Assert.That(\"}\", Is.Not.Empty); // }\n",
+        )
+        .replace(
+            "    public void TheYakIsShaved()\n    {\n        throw new NotImplementedException();",
+            "    public void TheYakIsShaved()\n    {\n        yak.Shaved = true;",
+        );
+    let generated = Feature::from_str(after)?.export(NUnit::default());
+    let merged = NUnit::merge(&existing, &generated);
+    assert!(without_hashes(&merged).contains(
+        "    [TestCase(@\"goat\")]
+    [TestCase(@\"sheep\")]
+    public void FeedAAnimal(string animal)
+    {
+        IFeedThe___(animal);
+        Assert.That(\"}\", Is.Not.Empty); // }
+    }
+    // gherkin: removed scenario Shave the yak
+    [Ignore(\"The scenario was removed from the feature file\")]
+    [Test]
+    public void ShaveTheYak()
+    {
+        TheYakIsShaved();
+    }
+    // gherkin: scenario Milk the goat
+    [Test]
+    public void MilkTheGoat()
+    {
+        TheGoatIsMilked();
+    }
+"
+    ));
+    assert!(merged.contains(
+        "    // gherkin: removed step TheYakIsShaved()
+    public void TheYakIsShaved()
+    {
+        yak.Shaved = true;
+    }
+"
+    ));
+    assert_eq!(NUnit::merge(&merged, &generated), merged);
+
+    // A scenario which comes back gets its body back too.
+    let restored = NUnit::merge(
+        &merged,
+        &Feature::from_str(before)?.export(NUnit::default()),
+    );
+    assert!(without_hashes(&restored).contains(
+        "    // gherkin: scenario Shave the yak
+    [Test]
+    public void ShaveTheYak()
+    {
+        TheYakIsShaved();
+    }
+"
+    ));
+    assert!(restored.contains("        yak.Shaved = true;\n"));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_merge_nunit_regenerates_unedited_code() -> Result<()> {
+    let before = r###"
+    Feature: Farm activities
+
+    Scenario: Shave a yak
+        When I shave the yak
+
+    Scenario: Sell the yak
+        When I sell the yak
+    "###;
+    let after = r###"
+    Feature: Farm activities
+
+    Scenario: Shave a yak!
+        When I shave the yak
+
+    Scenario: Sell the yak
+        When I sell the yak
+        Then I am rich
+    "###;
+    let existing = Feature::from_str(before)?.export(NUnit::default());
+    let generated = Feature::from_str(after)?.export(NUnit::default());
+    let merged = NUnit::merge(&existing, &generated);
+    // The test of the renamed scenario takes its name back from the removed one.
+    assert!(without_hashes(&merged).contains(
+        "    // gherkin: removed scenario Shave a yak
+    [Ignore(\"The scenario was removed from the feature file\")]
+    [Test]
+    public void ShaveAYak_2()
+    {
+        IShaveTheYak();
+    }
+    // gherkin: scenario Shave a yak!
+    [Test]
+    public void ShaveAYak()
+"
+    ));
+    // A body which hasn't been edited calls the steps added since.
+    assert!(merged.contains(
+        "    public void SellTheYak()
+    {
+        ISellTheYak();
+        IAmRich();
+    }
+"
+    ));
+    assert_eq!(NUnit::merge(&merged, &generated), merged);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_merge_nunit_keeps_added_code() -> Result<()> {
+    let feature = r###"
+    @fast
+    Feature: Farm activities
+
+    Scenario: Shave the yak
+        Then the yak is shaved
+
+    Scenario: Shave the yak
+        Then the yak is bald
+    "###;
+    let existing = Feature::from_str(feature)?
+        .export(NUnit::default())
+        .replace(
+            "using NUnit.Framework;\n",
+            "using NUnit.Framework;\nusing System.Linq;\n",
+        )
+        .replace(
+            "public class FarmActivities : FarmActivitiesSteps\n{\n",
+            "public class FarmActivities : FarmActivitiesSteps\n{\n    private readonly Yak yak = new Yak();\n",
+        )
+        .replace(
+            "    public void ShaveTheYak_2()\n    {\n        TheYakIsBald();",
+            "    public void ShaveTheYak_2() {\n        Assert.That(yak.Bald);",
+        )
+        .replace(
+            "        throw new NotImplementedException();\n    }\n\n",
+            "        throw new NotImplementedException();\n    }\n\n    \
+            private void Shave() => yak.Hair.Clear();\n\n",
+        );
+    let generated = Feature::from_str(&feature.replace("@fast", "@slow"))?.export(NUnit::default());
+    let merged = NUnit::merge(&existing, &generated);
+    let expected = existing.replace("fast", "slow").replace(
+        "    public void ShaveTheYak_2() {\n",
+        "    public void ShaveTheYak_2()\n    {\n",
+    );
+    assert_eq!(merged, expected);
+    assert_eq!(NUnit::merge(&merged, &generated), merged);
+    assert!(without_hashes(&merged).contains(
+        "    // gherkin: scenario Shave the yak #2
+    [Test]
+    public void ShaveTheYak_2()
+    {
+        Assert.That(yak.Bald);
+    }
+"
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_inconsistent_data_table() {
+    let input = r###"
+    Feature: Farm activities
+
+    Scenario: Count the animals
+        Given I have the following animals:
+            | animal | count |
+            | yak    |
+    "###;
+    match Feature::from_str(input) {
+        Err(ParseError::InconsistentTableWidth {
+            span,
+            expected: 2,
+            found: 1,
+        }) => assert_eq!(span.start, Location::new(7, 13)),
+        other => panic!(
+            "Expected an inconsistent table width, got {:?}",
+            other.err()
+        ),
+    }
+}
+
+#[test]
+fn test_load_localized_keywords() -> Result<()> {
+    let input = r###"
+    # language: fr
+    Fonctionnalité: Activités de la ferme
+
+    Plan du scénario: Tondre un animal
+        Étant donné qu'il y a un <animal>
+        Et qu'il a des poils
+        Quand je le tonds
+        Alors il n'a plus de poils
+    Exemples:
+        | animal |
+        | yak    |
+    "###;
+    let feature = Feature::from_str(input)?;
+    assert_eq!(feature.language, "fr");
+    assert_eq!(feature.keyword, "Fonctionnalité");
+    let outline = match &feature.items[0] {
+        FeatureItem::Outline(outline) => outline,
+        FeatureItem::Bare(_) => panic!("Expected a scenario outline"),
+    };
+    assert_eq!(outline.keyword, "Plan du scénario");
+    assert_eq!(outline.example_blocks[0].keyword, "Exemples");
+
+    let keywords = outline
+        .steps
+        .iter()
+        .map(|step| (step.keyword, step.keyword_text.as_ref()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        keywords,
+        vec![
+            (StepKeyword::Given, "Étant donné qu'"),
+            (StepKeyword::And, "Et qu'"),
+            (StepKeyword::When, "Quand "),
+            (StepKeyword::Then, "Alors "),
+        ]
+    );
+    assert_eq!(outline.steps[0].literals, vec!["il y a un ", ""]);
+    Ok(())
+}
+
+#[test]
+fn test_load_any_upstream_language() -> Result<()> {
+    let input = r###"
+    # language: pt
+    Funcionalidade: Atividades da fazenda
+
+    Cenário: Tosquiar um iaque
+        Dado que tenho um iaque
+        Quando eu o tosquio
+        Então ele não tem pelos
+    "###;
+    let feature = Feature::from_str(input)?;
+    assert_eq!(feature.language, "pt");
+    let scenario = match &feature.items[0] {
+        FeatureItem::Bare(scenario) => scenario,
+        FeatureItem::Outline(_) => panic!("Expected a scenario"),
+    };
+    assert_eq!(scenario.keyword, "Cenário");
+    assert_eq!(scenario.steps[2].keyword, StepKeyword::Then);
+    Ok(())
+}
+
+#[test]
+fn test_unsupported_language() {
+    let input = r###"
+    # language: no-such
+    Feature: Farm activities
+    "###;
+    match Feature::from_str(input) {
+        Err(ParseError::UnsupportedLanguage { language, .. }) => assert_eq!(language, "no-such"),
+        other => panic!("Expected an unsupported language, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn test_source_positions() -> Result<()> {
+    let input = "@Farm\n\
+    Feature: Farm activities\n\
+    \n\
+    \x20 Scenario Outline: Shave an animal\n\
+    \x20   Given I have a <animal>\n\
+    \x20     \"\"\"\n\
+    \x20     Razor\n\
+    \x20     \"\"\"\n\
+    \x20 @Mammal   @Big\n\
+    \x20 Examples:\n\
+    \x20   | animal |\n\
+    \x20   | yak    |\n";
+    let feature = Feature::from_str(input)?;
+    assert_eq!(feature.tags[0].span.start, Location::new(1, 1));
+    assert_eq!(feature.span.start, Location::new(2, 1));
+    assert_eq!(feature.span.end, Location::new(12, 15));
+
+    let outline = match &feature.items[0] {
+        FeatureItem::Outline(outline) => outline,
+        FeatureItem::Bare(_) => panic!("Expected a scenario outline"),
+    };
+    assert_eq!(
+        outline.span,
+        Span::new(Location::new(4, 3), Location::new(12, 15))
+    );
+    assert_eq!(
+        outline.steps[0].span,
+        Span::new(Location::new(5, 5), Location::new(8, 10))
+    );
+
+    let block = &outline.example_blocks[0];
+    assert_eq!(block.span.start, Location::new(10, 3));
+    assert_eq!(block.tags[0].span.start, Location::new(9, 3));
+    assert_eq!(
+        block.tags[1].span,
+        Span::new(Location::new(9, 13), Location::new(9, 17))
+    );
+    assert_eq!(block.labels.span.start, Location::new(11, 5));
+    assert_eq!(block.examples[0].span.start, Location::new(12, 5));
+    Ok(())
+}
+
+#[test]
+fn test_error_location() {
+    let input = r###"Feature: Farm activities
+
+    Scenario Outline: Shave an animal
+        Given I have a <animal>
+    Examples:
+        | animal |
+        | yak    | razor |
+    "###;
+    let error = format!("{:#}", Feature::from_str(input).unwrap_err());
+    assert!(error.contains("line 7, column 9"), "{}", error);
+}
+
+#[test]
+fn test_error_codes() {
+    let missing_examples = r###"Feature: Farm activities
+
+    Scenario Outline: Shave an animal
+        Given I have a <animal>
+    "###;
+    let error = Feature::from_str(missing_examples).unwrap_err();
+    assert_eq!(error.code(), "missing-examples");
+    assert_eq!(error.span().unwrap().start, Location::new(3, 5));
+    let (feature, errors) = Feature::from_str_recovering(missing_examples);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(feature.unwrap().items.len(), 1);
+
+    let missing_examples = r###"Feature: Farm activities
+
+    Scenario Outline: Shave an animal
+        Given I have a <animal>
+
+    Scenario: Feed the yak
+        Given I have a yak
+    "###;
+    let error = Feature::from_str(missing_examples).unwrap_err();
+    assert_eq!(error.code(), "missing-examples");
+    assert_eq!(error.span().unwrap().start, Location::new(3, 5));
+
+    let error = Feature::from_str("\n    # Nothing to see here\n").unwrap_err();
+    assert_eq!(error, ParseError::Empty);
+    assert_eq!(error.span(), None);
+}
+
+#[test]
+fn test_recover_from_errors() {
+    let input = r###"Feature: Farm activities
+
+    Background:
+        Given I am on the farm
+
+    Scenario: Shave a yak
+        Given I have a <razor
+        And I have a yak
+
+    Scenario Outline: Feed an animal
+        Given I have a <animal>
+    Examples:
+        | animal |
+        | yak    | hay |
+        | goat   |
+
+    Background:
+        Given I am somewhere else
+
+    Scenario: Milk a goat
+        Given I have a goat
+        Then I have milk
+    "###;
+    let (feature, errors) = Feature::from_str_recovering(input);
+    let codes: Vec<_> = errors.iter().map(|error| error.code()).collect();
+    assert_eq!(
+        codes,
+        [
+            "unterminated-placeholder",
+            "inconsistent-table-width",
+            "duplicate-background"
+        ]
+    );
+    assert_eq!(errors[1].span().unwrap().start, Location::new(14, 9));
+
+    let feature = feature.unwrap();
+    assert_eq!(feature.items.len(), 3);
+    match &feature.items[0] {
+        FeatureItem::Bare(scenario) => assert!(scenario.steps.is_empty()),
+        _ => panic!("Expected the partial scenario to be kept"),
+    }
+    match &feature.items[1] {
+        FeatureItem::Outline(outline) => {
+            assert_eq!(outline.example_blocks[0].examples.len(), 1);
+            assert_eq!(outline.example_blocks[0].examples[0].entries[0], "goat");
+        }
+        _ => panic!("Expected the scenario outline to be read"),
+    }
+    match &feature.items[2] {
+        FeatureItem::Bare(scenario) => {
+            assert_eq!(scenario.name, "Milk a goat");
+            assert_eq!(scenario.steps.len(), 2);
+        }
+        _ => panic!("Expected the last scenario to be read"),
+    }
+
+    let (feature, errors) = Feature::from_str_recovering("");
+    assert!(feature.is_none());
+    assert_eq!(errors, [ParseError::Empty]);
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_comments() -> Result<()> {
+    let input = r###"# JIRA-1234
+Feature: Farm activities
+
+    # TODO: shave more yaks
+    @slow
+    Scenario Outline: Shave an animal
+        Given I have a <animal>
+        # The razor must be sharp
+        When I shave it with a razor
+    Examples:
+        | animal |
+        | yak    |
+
+# The end
+"###;
+    let feature = Feature::from_str(input)?;
+    assert_eq!(feature.comments[0].text, "# JIRA-1234");
+    assert_eq!(feature.trailing_comments[0].content(), "The end");
+    let outline = match &feature.items[0] {
+        FeatureItem::Outline(outline) => outline,
+        _ => panic!("Expected a scenario outline"),
+    };
+    assert_eq!(outline.comments[0].content(), "TODO: shave more yaks");
+    assert_eq!(outline.comments[0].span.start, Location::new(4, 5));
+    assert!(outline.steps[0].comments.is_empty());
+    assert_eq!(
+        outline.steps[1].comments[0].text,
+        "# The razor must be sharp"
+    );
+
+    let json = serde_json::to_string(&feature)?;
+    assert!(json.contains("# TODO: shave more yaks"));
+
+    assert!(!feature.export(NUnit::default()).contains("JIRA-1234"));
+    let output = feature.export(NUnit {
+        comments: true,
+        ..NUnit::default()
+    });
+    assert!(
+        output.starts_with("using System;\nusing NUnit.Framework;\n\n// JIRA-1234\n[TestFixture]"),
+        "{}",
+        output
+    );
+    assert!(without_hashes(&output).contains(
+        "    // TODO: shave more yaks\n    // gherkin: scenario Shave an animal\n    [TestCase"
+    ));
+    assert!(output.contains("        // The razor must be sharp\n        IShaveItWithARazor();"));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_descriptions() -> Result<()> {
+    let input = r###"Feature: Farm activities
+
+    Background:
+        Every morning starts on the farm.
+        Given I am on the farm
+
+    Scenario: Shave a yak
+        Yaks get "hot" in summer.
+        Given I have a yak
+
+    Scenario Outline: Feed an animal
+        Hungry animals are unhappy.
+        Given I have a <animal>
+    Examples:
+        Animals we keep
+        | animal |
+        | goat   |
+    "###;
+    let feature = Feature::from_str(input)?;
+    let background = feature.background.as_ref().unwrap();
+    assert_eq!(
+        background.description,
+        ["Every morning starts on the farm."]
+    );
+    assert_eq!(background.steps.len(), 1);
+    match &feature.items[1] {
+        FeatureItem::Outline(outline) => {
+            assert_eq!(outline.description, ["Hungry animals are unhappy."]);
+            assert_eq!(outline.example_blocks[0].description, ["Animals we keep"]);
+            assert_eq!(outline.example_blocks[0].examples.len(), 1);
+        }
+        _ => panic!("Expected a scenario outline"),
+    }
+
+    let output = feature.export(NUnit::default());
+    assert!(output.contains(
+        "    [Test]\n    [Description(\"Yaks get \\\"hot\\\" in summer.\")]\n    public void ShaveAYak()"
+    ));
+    assert!(output.contains(
+        "    [TestCase(@\"goat\", Description=\"Animals we keep\")]\n    [Description(\"Hungry animals are unhappy.\")]\n"
+    ));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_named_examples() -> Result<()> {
+    let input = r###"Feature: Farm activities
+
+    Scenario Outline: Feed an animal
+        Given I have a <animal>
+    Examples: Edge cases
+        Animals which are hard to feed
+        | animal |
+        | goat   |
+    Examples:
+        | animal |
+        | yak    |
+    "###;
+    let feature = Feature::from_str(input)?;
+    match &feature.items[0] {
+        FeatureItem::Outline(outline) => {
+            assert_eq!(outline.example_blocks[0].name, "Edge cases");
+            assert_eq!(outline.example_blocks[1].name, "");
+        }
+        _ => panic!("Expected a scenario outline"),
+    }
+    assert!(serde_json::to_string(&feature)?.contains("\"name\":\"Edge cases\""));
+
+    let output = feature.export(NUnit::default());
+    assert!(output.contains(
+        "    [TestCase(@\"goat\", TestName=\"{m} (Edge cases){a}\", \
+        Description=\"Animals which are hard to feed\")]\n    [TestCase(@\"yak\")]\n"
+    ));
+    Ok(())
+}
+
+/// `code` exported as NUnit, without the hashes of method bodies in its markers.
+#[cfg(feature = "nunit")]
+fn without_hashes(code: &str) -> String {
+    let mut unhashed = String::new();
+    let mut rest = code;
+    while let Some(start) = rest.find("// gherkin[") {
+        let end = start + rest[start..].find("]:").unwrap();
+        unhashed += &rest[..start];
+        unhashed += "// gherkin";
+        rest = &rest[end + 1..];
+    }
+    unhashed + rest
+}
+
+fn tag_names<'t>(tags: &'t [crate::tags::Tag]) -> Vec<&'t str> {
+    tags.iter().map(|tag| tag.name.as_ref()).collect()
+}
+
+#[test]
+#[cfg(feature = "nunit")]
+fn test_tag_inheritance() -> Result<()> {
+    let input = r###"@farm
+Feature: Farm activities
+
+    @hungry
+    Scenario Outline: Feed an animal
+        Given I have a <animal>
+    @small
+    Examples:
+        | animal |
+        | goat   |
+
+    @slow @farm
+    Scenario: Shave a yak
+        Given I have a yak
+
+    @barn
+    Rule: Animals sleep in the barn
+
+        @night
+        Scenario: Put the animals to bed
+            Given it is night
+    "###;
+    let feature = Feature::from_str(input)?;
+    match &feature.items[0] {
+        FeatureItem::Outline(outline) => {
+            assert_eq!(tag_names(&outline.tags), ["hungry"]);
+            assert_eq!(tag_names(&outline.effective_tags), ["farm", "hungry"]);
+            let block = &outline.example_blocks[0];
+            assert_eq!(tag_names(&block.tags), ["small"]);
+            assert_eq!(
+                tag_names(&block.effective_tags),
+                ["farm", "hungry", "small"]
+            );
+        }
+        _ => panic!("Expected a scenario outline"),
+    }
+    match &feature.items[1] {
+        FeatureItem::Bare(scenario) => {
+            assert_eq!(tag_names(&scenario.tags), ["slow", "farm"]);
+            assert_eq!(tag_names(&scenario.effective_tags), ["farm", "slow"]);
+        }
+        _ => panic!("Expected a scenario"),
+    }
+    let rule = &feature.rules[0];
+    assert_eq!(tag_names(&rule.effective_tags), ["farm", "barn"]);
+    match &rule.items[0] {
+        FeatureItem::Bare(scenario) => {
+            assert_eq!(
+                tag_names(&scenario.effective_tags),
+                ["farm", "barn", "night"]
+            );
+        }
+        _ => panic!("Expected a scenario"),
+    }
+
+    let output = feature.export(NUnit::default());
+    assert!(output.starts_with(
+        "using System;\nusing NUnit.Framework;\n\n[TestFixture]\n[Category(\"farm\")]\npublic class"
+    ));
+    assert!(output.contains(
+        "    [TestCase(@\"goat\", Category=\"small\")]\n    [Category(\"hungry\")]\n    public void FeedAnAnimal("
+    ));
+    assert!(output.contains(
+        "    [Test]\n    [Category(\"slow\")]\n    [Category(\"farm\")]\n    public void ShaveAYak()"
+    ));
+    assert!(output.contains(
+        "    [TestFixture]\n    [Category(\"farm\")]\n    [Category(\"barn\")]\n    public class AnimalsSleepInTheBarn"
+    ));
+    assert!(output.contains("        [Test]\n        [Category(\"night\")]\n"));
+    Ok(())
+}
+
+#[test]
+fn test_compile_pickles() -> Result<()> {
+    let input = r###"@farm
+Feature: Farm activities
+
+    Background:
+        Given I am on the farm
+
+    Scenario: Shave a yak
+        Given I have a yak
+        And a <razor>
+        When I shave it
+
+    Scenario Outline: Feed the <animal>
+        Given I have a <animal>
+        And it eats:
+            | food   | amount   |
+            | <food> | <amount> |
+        Then it says:
+            """
+            <noise>!
+            """
+    @small
+    Examples:
+        | animal | food | amount | noise |
+        | goat   | hay  | 2      | baa   |
+        | hen    | corn | 1      | cluck |
+
+    Rule: Animals sleep at night
+
+        Background:
+            Given it is night
+
+        Scenario: Put the animals to bed
+            When I close the barn
+    "###;
+    let feature = Feature::from_str(input)?;
+    let pickles = compile(&feature);
+    let names: Vec<_> = pickles.iter().map(|pickle| pickle.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "Shave a yak",
+            "Feed the goat",
+            "Feed the hen",
+            "Put the animals to bed"
+        ]
+    );
+
+    let shave = &pickles[0];
+    let texts: Vec<_> = shave.steps.iter().map(|step| step.text.as_str()).collect();
+    assert_eq!(
+        texts,
+        [
+            "I am on the farm",
+            "I have a yak",
+            "a <razor>",
+            "I shave it"
+        ]
+    );
+    assert_eq!(shave.steps[2].step_type, PickleStepType::Context);
+    assert_eq!(shave.steps[3].step_type, PickleStepType::Action);
+    assert_eq!(shave.id, "pickle:7:5");
+    assert_eq!(shave.steps[0].id, "pickle:7:5/5:9");
+    assert_eq!(tag_names(&shave.tags), ["farm"]);
+
+    let hen = &pickles[2];
+    assert_eq!(hen.id, "pickle:12:5;25:9");
+    assert_eq!(hen.ast_node_ids, ["12:5", "25:9"]);
+    assert_eq!(tag_names(&hen.tags), ["farm", "small"]);
+    assert_eq!(hen.steps[1].text, "I have a hen");
+    assert_eq!(hen.steps[1].ast_node_ids, ["13:9", "25:9"]);
+    match &hen.steps[2].argument {
+        Some(PickleArgument::DataTable(rows)) => {
+            assert_eq!(rows[1], ["corn", "1"]);
+        }
+        other => panic!("Expected a data table, got {:?}", other),
+    }
+    match &hen.steps[3].argument {
+        Some(PickleArgument::DocString { content, .. }) => assert_eq!(content, "cluck!"),
+        other => panic!("Expected a doc string, got {:?}", other),
+    }
+
+    let bed = &pickles[3];
+    let texts: Vec<_> = bed.steps.iter().map(|step| step.text.as_str()).collect();
+    assert_eq!(
+        texts,
+        ["I am on the farm", "it is night", "I close the barn"]
+    );
+
+    assert_eq!(
+        serde_json::to_string(&pickles)?,
+        serde_json::to_string(&compile(&Feature::from_str(input)?))?
+    );
+    Ok(())
+}
+
+#[test]
+fn test_substitute_placeholders_once() -> Result<()> {
+    let input = r###"Feature: Farm activities
+
+    Scenario Outline: Feed the <animal> some <food>
+        Given it eats:
+            """
+            <food> for <<animal>>
+            """
+    Examples:
+        | animal   | food   |
+        | <food>   | hay    |
+    "###;
+    let pickles = compile(&Feature::from_str(input)?);
+    assert_eq!(pickles[0].name, "Feed the <food> some hay");
+    match &pickles[0].steps[0].argument {
+        Some(PickleArgument::DocString { content, .. }) => {
+            assert_eq!(content, "hay for <<food>>")
+        }
+        other => panic!("Expected a doc string, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[cfg(feature = "messages")]
+#[test]
+fn test_messages() -> Result<()> {
+    let input = r###"# language: en
+@farm
+Feature: Farm activities
+
+  Background:
+    Given I am on the farm
+
+  # Feeding
+  Scenario Outline: Feed the <animal>
+    When I feed the <animal> <food>
+  Examples:
+    | animal | food |
+    | goat   | hay  |
+"###;
+    let feature = Feature::from_str(input)?;
+    let output = feature.export(Messages {
+        uri: "farm.feature",
+        source: input,
+    });
+    let envelopes = output
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+    assert_eq!(envelopes.len(), 3);
+    assert_eq!(envelopes[0]["source"]["data"], input);
+
+    let document = &envelopes[1]["gherkinDocument"];
+    assert_eq!(document["comments"][0]["text"], "  # Feeding");
+    assert_eq!(document["feature"]["tags"][0]["name"], "@farm");
+    let children = &document["feature"]["children"];
+    assert_eq!(
+        children[0]["background"]["steps"][0]["keywordType"],
+        "Context"
+    );
+    let outline = &children[1]["scenario"];
+    assert_eq!(outline["steps"][0]["text"], "I feed the <animal> <food>");
+    let header = &outline["examples"][0]["tableHeader"];
+    assert_eq!(header["cells"][1]["value"], "food");
+    assert_eq!(
+        header["cells"][1]["location"],
+        serde_json::json!({ "line": 12, "column": 16 })
+    );
+
+    let pickle = &envelopes[2]["pickle"];
+    assert_eq!(pickle["name"], "Feed the goat");
+    assert_eq!(pickle["steps"][1]["text"], "I feed the goat hay");
+    assert_eq!(pickle["astNodeIds"], serde_json::json!(["9:3", "13:5"]));
+    assert_eq!(
+        output,
+        feature.export(Messages {
+            uri: "farm.feature",
+            source: input,
+        })
+    );
+
+    // A comment without a span keeps its own text.
+    let feature: Feature = serde_json::from_str(
+        r###"{"keyword": "Feature", "name": "Farm", "comments": [{"text": "# Imported"}]}"###,
+    )?;
+    let output = feature.export(Messages {
+        uri: "farm.feature",
+        source: "",
+    });
+    let document: serde_json::Value = serde_json::from_str(output.lines().nth(1).unwrap())?;
+    assert_eq!(
+        document["gherkinDocument"]["comments"][0]["text"],
+        "# Imported"
+    );
+    Ok(())
+}
+
+#[cfg(feature = "messages")]
+#[test]
+fn test_messages_ids_are_unique() -> Result<()> {
+    fn collect_ids<'v>(value: &'v serde_json::Value, ids: &mut Vec<&'v str>) {
+        match value {
+            serde_json::Value::Object(object) => {
+                if let Some(serde_json::Value::String(id)) = object.get("id") {
+                    ids.push(id);
+                }
+                object.values().for_each(|value| collect_ids(value, ids));
+            }
+            serde_json::Value::Array(values) => {
+                values.iter().for_each(|value| collect_ids(value, ids))
+            }
+            _ => {}
+        }
+    }
+
+    for (uri, source, _) in conformance_cases("good", ".ast.ndjson")? {
+        let output = Feature::from_str(&source)?.export(Messages {
+            uri: &uri,
+            source: &source,
+        });
+        let envelopes = output
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<serde_json::Value>, _>>()?;
+        let mut ids = vec![];
+        envelopes
+            .iter()
+            .for_each(|envelope| collect_ids(envelope, &mut ids));
+        let count = ids.len();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), count, "{}", uri);
+    }
+    Ok(())
+}
+
+/// The `.feature` files in `testdata/<kind>`, with the contents of the expected
+/// output file beside each one, named with `extension` after the feature's name.
+#[cfg(feature = "messages")]
+fn conformance_cases(kind: &str, extension: &str) -> Result<Vec<(String, String, String)>> {
+    let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("testdata")
+        .join(kind);
+    let mut cases = vec![];
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension() == Some("feature".as_ref()) {
+            let mut expected_path = path.clone().into_os_string();
+            expected_path.push(extension);
+            cases.push((
+                format!(
+                    "testdata/{}/{}",
+                    kind,
+                    path.file_name().unwrap().to_string_lossy()
+                ),
+                std::fs::read_to_string(&path)?,
+                std::fs::read_to_string(expected_path)?,
+            ));
+        }
+    }
+    cases.sort();
+    Ok(cases)
+}
+
+#[cfg(feature = "messages")]
+#[test]
+fn test_conformance_good() -> Result<()> {
+    use crate::export::GherkinDocument;
+    let cases = conformance_cases("good", ".ast.ndjson")?;
+    assert!(!cases.is_empty());
+    for (uri, source, expected) in cases {
+        let feature = Feature::from_str(&source)?;
+        let actual: serde_json::Value = serde_json::from_str(&feature.export(GherkinDocument {
+            uri: &uri,
+            source: &source,
+        }))?;
+        let expected: serde_json::Value = serde_json::from_str(&expected)?;
+        // The URI depends on where the reference parsers were run from.
+        for field in ["feature", "comments"] {
+            assert_eq!(
+                actual["gherkinDocument"][field], expected["gherkinDocument"][field],
+                "{} differs in {}",
+                uri, field
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Error messages are worded differently from the reference parsers',
+/// so only where errors are found is compared.
+#[cfg(feature = "messages")]
+#[test]
+fn test_conformance_bad() -> Result<()> {
+    let cases = conformance_cases("bad", ".errors.ndjson")?;
+    assert!(!cases.is_empty());
+    for (uri, source, expected) in cases {
+        let (_, errors) = Feature::from_str_recovering(&source);
+        let actual: Vec<_> = errors
+            .iter()
+            .map(|error| {
+                error
+                    .span()
+                    .map(|span| (span.start.line, span.start.column))
+            })
+            .collect();
+        let expected = expected
+            .lines()
+            .map(|line| {
+                let error: serde_json::Value = serde_json::from_str(line)?;
+                let location = &error["parseError"]["source"]["location"];
+                Ok(Some((
+                    location["line"].as_u64().unwrap_or_default() as usize,
+                    location["column"].as_u64().unwrap_or_default() as usize,
+                )))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(actual, expected, "{} differs", uri);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "gherkin")]
+#[test]
+fn test_format_gherkin() -> Result<()> {
+    use crate::export::Gherkin;
+    let input = r###"# language: fr
+  @farm   @animals
+Fonctionnalité:   Farm activities
+      Feeding everyone
+# Feeding
+    Plan du scénario: Feed the <animal>
+  Quand I feed the <animal>
+            """json
+            {"food": "<food>"}
+            """
+    @small
+ Exemples:
+  |animal|food|
+  |   goat|hay \| straw|
+  |鶏|corn|
+  Scénario: Rest
+  Alors nothing happens
+"###;
+    let expected = r###"# language: fr
+@farm @animals
+Fonctionnalité: Farm activities
+  Feeding everyone
+
+  # Feeding
+  Plan du scénario: Feed the <animal>
+    Quand I feed the <animal>
+      """json
+      {"food": "<food>"}
+      """
+
+    @small
+    Exemples:
+      | animal | food         |
+      | goat   | hay \| straw |
+      | 鶏     | corn         |
+
+  Scénario: Rest
+    Alors nothing happens
+"###;
+    let formatted = Feature::from_str(input)?.export(Gherkin);
+    assert_eq!(formatted, expected);
+    assert!(Gherkin::preserves(input, &formatted));
+    assert_eq!(Feature::from_str(&formatted)?.export(Gherkin), expected);
+    Ok(())
+}
+
+#[cfg(feature = "gherkin")]
+#[test]
+fn test_format_keeps_paragraphs_and_row_comments() -> Result<()> {
+    use crate::export::Gherkin;
+    let input = r###"Feature: Farm activities
+  Everyone is fed.
+
+  Twice a day.
+
+  Scenario Outline: Feed the <animal>
+    Given I have a <animal>
+      # Hungry
+      | food |
+      | hay  |
+
+    Examples:
+      | animal |
+      # | hen    |
+      | goat   |
+
+  Scenario: Rest
+    Then nothing happens
+"###;
+    let feature = Feature::from_str(input)?;
+    assert_eq!(feature.free_text, ["Everyone is fed.", "", "Twice a day."]);
+    assert_eq!(feature.export(Gherkin), input);
+    assert!(Gherkin::preserves(input, input));
+
+    // A comment partway through a description has nowhere to be kept.
+    let input = "Feature: Farm activities\n  Everyone is fed.\n  # Twice a day?\n  Always.\n\n  Scenario: Rest\n    Then nothing happens\n";
+    let formatted = Feature::from_str(input)?.export(Gherkin);
+    assert!(!Gherkin::preserves(input, &formatted));
+    Ok(())
+}
+
+#[cfg(feature = "gherkin")]
+#[test]
+fn test_feature_from_json() -> Result<()> {
+    use crate::export::Gherkin;
+    // Strings with escapes can't be borrowed from the JSON, and nodes which
+    // weren't read from a file have no spans.
+    let json = r###"{
+        "keyword": "Feature",
+        "name": "Imported \"tests\"",
+        "free_text": ["From the café"],
+        "tags": [{"name": "imported"}],
+        "items": [{"Outline": {
+            "keyword": "Scenario Outline",
+            "name": "Order a <drink>",
+            "steps": [
+                {"keyword": "When", "keyword_text": "When ", "literals": ["I order a ", ""], "variables": ["drink"]},
+                {"keyword": "Then", "keyword_text": "Then ", "literals": ["I receive"],
+                 "doc_string": {"content_type": "json", "content": "{\"drink\": \"<drink>\"}"}}
+            ],
+            "example_blocks": [{
+                "keyword": "Examples",
+                "name": "",
+                "labels": {"entries": ["drink"]},
+                "examples": [{"entries": ["tea | milk"]}]
+            }]
+        }}]
+    }"###;
+    let feature: Feature<'static> = serde_json::from_reader(json.as_bytes())?;
+    let expected = r###"@imported
+Feature: Imported "tests"
+  From the café
+
+  Scenario Outline: Order a <drink>
+    When I order a <drink>
+    Then I receive
+      """json
+      {"drink": "<drink>"}
+      """
+
+    Examples:
+      | drink       |
+      | tea \| milk |
+"###;
+    assert_eq!(feature.export(Gherkin), expected);
+
+    // Effective tags are worked out again, and nodes without spans still get distinct IDs.
+    let pickles = compile(&feature);
+    let tags: Vec<_> = pickles[0].tags.iter().map(|tag| &tag.name).collect();
+    assert_eq!(tags, ["imported"]);
+    assert_eq!(pickles[0].id, "pickle:#i0;#i0.e0.0");
+    let step_ids: Vec<_> = pickles[0].steps.iter().map(|step| &step.id).collect();
+    assert_eq!(
+        step_ids,
+        ["pickle:#i0;#i0.e0.0/#i0.s0", "pickle:#i0;#i0.e0.0/#i0.s1"]
+    );
+
+    // A feature exported as JSON comes back as the same feature.
+    let input = std::fs::read_to_string("testdata/good/rule.feature")?;
+    let parsed = Feature::from_str(&input)?;
+    let json = serde_json::to_string(&parsed)?;
+    let feature: Feature<'static> = serde_json::from_str(&json)?;
+    assert_eq!(feature.export(Gherkin), parsed.export(Gherkin));
+    Ok(())
+}
+
+#[test]
+fn test_into_owned() -> Result<()> {
+    fn read(path: &str) -> Result<Feature<'static>> {
+        let input = std::fs::read_to_string(path)?;
+        Ok(Feature::from_str(&input)?.into_owned())
+    }
+    let feature = read("testdata/good/rule.feature")?;
+    let names = std::thread::spawn(move || {
+        feature.rules[0]
+            .items
+            .iter()
+            .map(|item| match item {
+                FeatureItem::Bare(scenario) => scenario.name.to_string(),
+                FeatureItem::Outline(outline) => outline.name.to_string(),
+            })
+            .collect::<Vec<_>>()
+    })
+    .join()
+    .expect("The thread doesn't panic");
+    assert!(!names.is_empty());
+
+    let input = std::fs::read_to_string("testdata/good/scenario_outline.feature")?;
+    let feature = Feature::from_str(&input)?;
+    let expected = serde_json::to_string(&feature)?;
+    let owned = feature.into_owned();
+    drop(input);
+    assert_eq!(serde_json::to_string(&owned)?, expected);
+    Ok(())
+}
+
+#[test]
+fn test_visit() -> Result<()> {
+    use crate::tags::Tag;
+    use crate::visit::{walk_outline, walk_step, Visit, VisitMut};
+    use crate::ScenarioOutline;
+
+    /// Counts the tags, rows and steps, except for those of plain scenarios.
+    #[derive(Default)]
+    struct Counter<'ast> {
+        tags: Vec<&'ast str>,
+        rows: usize,
+        steps: usize,
+    }
+
+    impl<'ast, 'a> Visit<'ast, 'a> for Counter<'ast> {
+        fn visit_scenario(&mut self, _scenario: &'ast crate::Scenario<'a>) {}
+
+        fn visit_outline(&mut self, outline: &'ast ScenarioOutline<'a>) {
+            walk_outline(self, outline);
+        }
+
+        fn visit_row(&mut self, _row: &'ast crate::ExampleRow<'a>) {
+            self.rows += 1;
+        }
+
+        fn visit_step(&mut self, step: &'ast crate::Step<'a>) {
+            self.steps += 1;
+            walk_step(self, step);
+        }
+
+        fn visit_tag(&mut self, tag: &'ast Tag<'a>) {
+            self.tags.push(&tag.name);
+        }
+    }
+
+    /// Renames every `@wip` tag to `@pending`.
+    struct Renamer;
+
+    impl<'a> VisitMut<'a> for Renamer {
+        fn visit_tag_mut(&mut self, tag: &mut Tag<'a>) {
+            if tag.name == "wip" {
+                tag.name = "pending".into();
+            }
+        }
+    }
+
+    let input = r###"@farm
+Feature: Farm activities
+  Background:
+    Given I have a yak
+
+  @wip
+  Scenario: Shave a yak
+    When I shave the yak
+
+  Rule: Feeding
+    @wip @feeding
+    Scenario Outline: Feed a <animal>
+      When I feed a <animal>
+        | food |
+        | hay  |
+
+      @small
+      Examples:
+        | animal |
+        | goat   |
+        | chick  |
+"###;
+    let mut feature = Feature::from_str(input)?;
+    let mut counter = Counter::default();
+    counter.visit_feature(&feature);
+    assert_eq!(counter.tags, vec!["farm", "wip", "feeding", "small"]);
+    assert_eq!(counter.rows, 5);
+    assert_eq!(counter.steps, 2);
+
+    Renamer.visit_feature_mut(&mut feature);
+    let mut counter = Counter::default();
+    counter.visit_feature(&feature);
+    assert_eq!(counter.tags, vec!["farm", "pending", "feeding", "small"]);
+    match &feature.items[0] {
+        FeatureItem::Bare(scenario) => {
+            assert_eq!(tag_names(&scenario.tags), vec!["pending"]);
+            assert_eq!(tag_names(&scenario.effective_tags), vec!["farm", "pending"]);
+        }
+        FeatureItem::Outline(_) => panic!("Expected a scenario"),
+    }
+
+    // Tags changed by hand are inherited once the effective tags are worked out again.
+    feature.tags[0].name = "barn".into();
+    feature.inherit_tags();
+    let tags: Vec<_> = compile(&feature)
+        .iter()
+        .map(|pickle| tag_names(&pickle.tags).join(" "))
+        .collect();
+    assert_eq!(
+        tags,
+        [
+            "barn pending",
+            "barn pending feeding small",
+            "barn pending feeding small"
+        ]
+    );
+    #[cfg(feature = "nunit")]
+    {
+        let output = feature.export(NUnit::default());
+        assert!(output.contains("    [Category(\"barn\")]\n    public class Feeding"));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_syntax_tree() -> Result<()> {
+    use crate::cst::{LineKind, NodeKind, SyntaxTree};
+    use std::borrow::Cow;
+    let input = "# language: en\r\n@farm\r\nFeature:  Farm activities   \r\n  Feeding everyone\r\n\r\n\t# Feeding\r\n  Example: Feed the goat\r\n    Given a goat\r\n      | hay |  straw\\|oats |\r\n    When I feed it\n      ```\n      # not a comment\n      ```\n\n  Scenarios:   \n  Examples: orphan\n# the end";
+    let mut tree = SyntaxTree::parse(input)?;
+    assert_eq!(tree.to_string(), input);
+
+    let feature = tree.root.nodes().next().unwrap();
+    assert_eq!(feature.kind, NodeKind::Feature);
+    let kinds: Vec<_> = feature.lines().iter().map(|line| line.kind).collect();
+    assert_eq!(
+        kinds[..7],
+        [
+            LineKind::Language,
+            LineKind::Tags,
+            LineKind::Header(NodeKind::Feature),
+            LineKind::Other,
+            LineKind::Blank,
+            LineKind::Comment,
+            LineKind::Header(NodeKind::Scenario),
+        ]
+    );
+    let scenario = feature.nodes().next().unwrap();
+    assert_eq!(scenario.header().unwrap().text, "  Example: Feed the goat");
+    let steps: Vec<_> = scenario.nodes().map(|node| node.lines().len()).collect();
+    assert_eq!(steps, [2, 4, 2, 1]);
+    assert_eq!(tree.root.lines().last().unwrap().kind, LineKind::Comment,);
+
+    let scenario = tree
+        .root
+        .nodes_mut()
+        .next()
+        .unwrap()
+        .nodes_mut()
+        .next()
+        .unwrap();
+    scenario.header_mut().unwrap().text = Cow::Borrowed("  Example: Feed the sheep");
+    let edited = tree.to_string();
+    assert_eq!(edited, input.replace("the goat\r\n", "the sheep\r\n"));
+
+    let feature =
+        SyntaxTree::parse("Feature: Farm\n\n  Scenario: Feed the goat\n    Given a goat\n")?;
+    let feature = feature.to_feature()?;
+    assert_eq!(feature.name, "Farm");
+    assert_eq!(feature.items.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_syntax_tree_agrees_with_ast() -> Result<()> {
+    use crate::cst::{LineKind, SyntaxTree};
+    use crate::{Step, Visit};
+
+    #[derive(Default)]
+    struct StepCounter {
+        steps: usize,
+    }
+
+    impl<'ast, 'a> Visit<'ast, 'a> for StepCounter {
+        fn visit_step(&mut self, _step: &'ast Step<'a>) {
+            self.steps += 1;
+        }
+    }
+
+    let directory = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/good");
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension() != Some("feature".as_ref()) {
+            continue;
+        }
+        let source = std::fs::read_to_string(&path)?;
+        let tree = SyntaxTree::parse(&source)?;
+        assert_eq!(tree.to_string(), source, "{}", path.display());
+        let steps = tree
+            .root
+            .lines()
+            .iter()
+            .filter(|line| matches!(line.kind, LineKind::Step(_)))
+            .count();
+        let mut counter = StepCounter::default();
+        counter.visit_feature(&Feature::from_str(&source)?);
+        assert_eq!(steps, counter.steps, "{}", path.display());
+    }
+    Ok(())
 }
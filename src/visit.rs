@@ -0,0 +1,339 @@
+//! Traversing the AST, without matching on every kind of node by hand.
+//!
+//! Implement [`Visit`] (or [`VisitMut`] to change nodes in place), overriding
+//! the methods for the nodes of interest. Each method walks the nodes within
+//! its node by default, through the `walk_*` function of the same name, so an
+//! override which still wants the nodes within should call that function.
+//!
+//! ```
+//! use gherkin_reader::{Feature, Step, Visit};
+//!
+//! #[derive(Default)]
+//! struct StepCounter {
+//!     steps: usize,
+//! }
+//!
+//! impl<'ast, 'a> Visit<'ast, 'a> for StepCounter {
+//!     fn visit_step(&mut self, _step: &'ast Step<'a>) {
+//!         self.steps += 1;
+//!     }
+//! }
+//!
+//! let feature = Feature::from_str(
+//!     "Feature: Farm activities
+//!
+//!     Background:
+//!         Given I have a yak
+//!
+//!     Scenario: Shave a yak
+//!         When I shave the yak
+//!         Then I have yak hair",
+//! )?;
+//! let mut counter = StepCounter::default();
+//! counter.visit_feature(&feature);
+//! assert_eq!(counter.steps, 3);
+//! # Ok::<(), gherkin_reader::ParseError>(())
+//! ```
+
+use crate::feature::{
+    ExampleBlock, ExampleRow, Feature, FeatureItem, Rule, Scenario, ScenarioOutline,
+};
+use crate::step::Step;
+use crate::tags::Tag;
+
+/// Visits the nodes of a feature in turn, by reference.
+///
+/// Nodes are visited in the order of the fields holding them, so a feature's
+/// background comes before its scenarios, and its scenarios before its rules.
+/// Only the tags written on a node are visited, not those it inherits.
+pub trait Visit<'ast, 'a> {
+    fn visit_feature(&mut self, feature: &'ast Feature<'a>) {
+        walk_feature(self, feature)
+    }
+
+    fn visit_rule(&mut self, rule: &'ast Rule<'a>) {
+        walk_rule(self, rule)
+    }
+
+    fn visit_background(&mut self, background: &'ast Scenario<'a>) {
+        walk_background(self, background)
+    }
+
+    /// Visits a scenario or scenario outline, through [`Visit::visit_scenario`]
+    /// or [`Visit::visit_outline`].
+    fn visit_item(&mut self, item: &'ast FeatureItem<'a>) {
+        walk_item(self, item)
+    }
+
+    fn visit_scenario(&mut self, scenario: &'ast Scenario<'a>) {
+        walk_scenario(self, scenario)
+    }
+
+    fn visit_outline(&mut self, outline: &'ast ScenarioOutline<'a>) {
+        walk_outline(self, outline)
+    }
+
+    fn visit_examples(&mut self, block: &'ast ExampleBlock<'a>) {
+        walk_examples(self, block)
+    }
+
+    /// Visits a row of an examples block, including its labels, or of a data table.
+    fn visit_row(&mut self, _row: &'ast ExampleRow<'a>) {}
+
+    fn visit_step(&mut self, step: &'ast Step<'a>) {
+        walk_step(self, step)
+    }
+
+    fn visit_tag(&mut self, _tag: &'ast Tag<'a>) {}
+}
+
+pub fn walk_feature<'ast, 'a, V>(visitor: &mut V, feature: &'ast Feature<'a>)
+where
+    V: Visit<'ast, 'a> + ?Sized,
+{
+    for tag in &feature.tags {
+        visitor.visit_tag(tag);
+    }
+    if let Some(background) = &feature.background {
+        visitor.visit_background(background);
+    }
+    for item in &feature.items {
+        visitor.visit_item(item);
+    }
+    for rule in &feature.rules {
+        visitor.visit_rule(rule);
+    }
+}
+
+pub fn walk_rule<'ast, 'a, V>(visitor: &mut V, rule: &'ast Rule<'a>)
+where
+    V: Visit<'ast, 'a> + ?Sized,
+{
+    for tag in &rule.tags {
+        visitor.visit_tag(tag);
+    }
+    if let Some(background) = &rule.background {
+        visitor.visit_background(background);
+    }
+    for item in &rule.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_background<'ast, 'a, V>(visitor: &mut V, background: &'ast Scenario<'a>)
+where
+    V: Visit<'ast, 'a> + ?Sized,
+{
+    for step in &background.steps {
+        visitor.visit_step(step);
+    }
+}
+
+pub fn walk_item<'ast, 'a, V>(visitor: &mut V, item: &'ast FeatureItem<'a>)
+where
+    V: Visit<'ast, 'a> + ?Sized,
+{
+    match item {
+        FeatureItem::Bare(scenario) => visitor.visit_scenario(scenario),
+        FeatureItem::Outline(outline) => visitor.visit_outline(outline),
+    }
+}
+
+pub fn walk_scenario<'ast, 'a, V>(visitor: &mut V, scenario: &'ast Scenario<'a>)
+where
+    V: Visit<'ast, 'a> + ?Sized,
+{
+    for tag in &scenario.tags {
+        visitor.visit_tag(tag);
+    }
+    for step in &scenario.steps {
+        visitor.visit_step(step);
+    }
+}
+
+pub fn walk_outline<'ast, 'a, V>(visitor: &mut V, outline: &'ast ScenarioOutline<'a>)
+where
+    V: Visit<'ast, 'a> + ?Sized,
+{
+    for tag in &outline.tags {
+        visitor.visit_tag(tag);
+    }
+    for step in &outline.steps {
+        visitor.visit_step(step);
+    }
+    for block in &outline.example_blocks {
+        visitor.visit_examples(block);
+    }
+}
+
+pub fn walk_examples<'ast, 'a, V>(visitor: &mut V, block: &'ast ExampleBlock<'a>)
+where
+    V: Visit<'ast, 'a> + ?Sized,
+{
+    for tag in &block.tags {
+        visitor.visit_tag(tag);
+    }
+    visitor.visit_row(&block.labels);
+    for row in &block.examples {
+        visitor.visit_row(row);
+    }
+}
+
+pub fn walk_step<'ast, 'a, V>(visitor: &mut V, step: &'ast Step<'a>)
+where
+    V: Visit<'ast, 'a> + ?Sized,
+{
+    if let Some(table) = &step.data_table {
+        for row in &table.rows {
+            visitor.visit_row(row);
+        }
+    }
+}
+
+/// Visits the nodes of a feature in turn, so that they can be changed in place.
+///
+/// Nodes are visited in the same order as by [`Visit`]. Tags inherited by a
+/// node are copies of the tags they were inherited from, so changing a tag
+/// doesn't change the tags which inherit it until [`walk_feature_mut`] works
+/// out the effective tags of every node again, once every node has been visited.
+pub trait VisitMut<'a> {
+    fn visit_feature_mut(&mut self, feature: &mut Feature<'a>) {
+        walk_feature_mut(self, feature)
+    }
+
+    fn visit_rule_mut(&mut self, rule: &mut Rule<'a>) {
+        walk_rule_mut(self, rule)
+    }
+
+    fn visit_background_mut(&mut self, background: &mut Scenario<'a>) {
+        walk_background_mut(self, background)
+    }
+
+    fn visit_item_mut(&mut self, item: &mut FeatureItem<'a>) {
+        walk_item_mut(self, item)
+    }
+
+    fn visit_scenario_mut(&mut self, scenario: &mut Scenario<'a>) {
+        walk_scenario_mut(self, scenario)
+    }
+
+    fn visit_outline_mut(&mut self, outline: &mut ScenarioOutline<'a>) {
+        walk_outline_mut(self, outline)
+    }
+
+    fn visit_examples_mut(&mut self, block: &mut ExampleBlock<'a>) {
+        walk_examples_mut(self, block)
+    }
+
+    fn visit_row_mut(&mut self, _row: &mut ExampleRow<'a>) {}
+
+    fn visit_step_mut(&mut self, step: &mut Step<'a>) {
+        walk_step_mut(self, step)
+    }
+
+    fn visit_tag_mut(&mut self, _tag: &mut Tag<'a>) {}
+}
+
+pub fn walk_feature_mut<'a, V>(visitor: &mut V, feature: &mut Feature<'a>)
+where
+    V: VisitMut<'a> + ?Sized,
+{
+    for tag in &mut feature.tags {
+        visitor.visit_tag_mut(tag);
+    }
+    if let Some(background) = &mut feature.background {
+        visitor.visit_background_mut(background);
+    }
+    for item in &mut feature.items {
+        visitor.visit_item_mut(item);
+    }
+    for rule in &mut feature.rules {
+        visitor.visit_rule_mut(rule);
+    }
+    feature.inherit_tags();
+}
+
+pub fn walk_rule_mut<'a, V>(visitor: &mut V, rule: &mut Rule<'a>)
+where
+    V: VisitMut<'a> + ?Sized,
+{
+    for tag in &mut rule.tags {
+        visitor.visit_tag_mut(tag);
+    }
+    if let Some(background) = &mut rule.background {
+        visitor.visit_background_mut(background);
+    }
+    for item in &mut rule.items {
+        visitor.visit_item_mut(item);
+    }
+}
+
+pub fn walk_background_mut<'a, V>(visitor: &mut V, background: &mut Scenario<'a>)
+where
+    V: VisitMut<'a> + ?Sized,
+{
+    for step in &mut background.steps {
+        visitor.visit_step_mut(step);
+    }
+}
+
+pub fn walk_item_mut<'a, V>(visitor: &mut V, item: &mut FeatureItem<'a>)
+where
+    V: VisitMut<'a> + ?Sized,
+{
+    match item {
+        FeatureItem::Bare(scenario) => visitor.visit_scenario_mut(scenario),
+        FeatureItem::Outline(outline) => visitor.visit_outline_mut(outline),
+    }
+}
+
+pub fn walk_scenario_mut<'a, V>(visitor: &mut V, scenario: &mut Scenario<'a>)
+where
+    V: VisitMut<'a> + ?Sized,
+{
+    for tag in &mut scenario.tags {
+        visitor.visit_tag_mut(tag);
+    }
+    for step in &mut scenario.steps {
+        visitor.visit_step_mut(step);
+    }
+}
+
+pub fn walk_outline_mut<'a, V>(visitor: &mut V, outline: &mut ScenarioOutline<'a>)
+where
+    V: VisitMut<'a> + ?Sized,
+{
+    for tag in &mut outline.tags {
+        visitor.visit_tag_mut(tag);
+    }
+    for step in &mut outline.steps {
+        visitor.visit_step_mut(step);
+    }
+    for block in &mut outline.example_blocks {
+        visitor.visit_examples_mut(block);
+    }
+}
+
+pub fn walk_examples_mut<'a, V>(visitor: &mut V, block: &mut ExampleBlock<'a>)
+where
+    V: VisitMut<'a> + ?Sized,
+{
+    for tag in &mut block.tags {
+        visitor.visit_tag_mut(tag);
+    }
+    visitor.visit_row_mut(&mut block.labels);
+    for row in &mut block.examples {
+        visitor.visit_row_mut(row);
+    }
+}
+
+pub fn walk_step_mut<'a, V>(visitor: &mut V, step: &mut Step<'a>)
+where
+    V: VisitMut<'a> + ?Sized,
+{
+    if let Some(table) = &mut step.data_table {
+        for row in &mut table.rows {
+            visitor.visit_row_mut(row);
+        }
+    }
+}
@@ -0,0 +1,5 @@
+#language:no-such
+
+Feature: Invalid language
+  Scenario: Minimal
+    Given the minimalism
@@ -0,0 +1,13 @@
+@a_tag
+
+Feature: Multiple parser errors
+
+  Scenario: minimalistic
+    Given the minimalism
+
+  non-blank line between scenarios
+
+  Scenario: minimalistic
+    Given the minimalism
+
+  another non-blank line between scenarios
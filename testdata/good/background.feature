@@ -0,0 +1,7 @@
+Feature: Background
+
+  Background: a simple background
+    Given the minimalism inside a background
+
+  Scenario: minimalistic
+    Given the minimalism
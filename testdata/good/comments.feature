@@ -0,0 +1,8 @@
+# A comment before the feature
+Feature: Comments
+
+  # A comment before the scenario
+  Scenario: commented
+    # A comment between steps
+    Given the minimalism
+    # A comment at the end
@@ -0,0 +1,5 @@
+# language: fr
+Fonctionnalité: i18n support
+
+  Scénario: Support des caractères spéciaux
+    Soit un exemple de scénario en français
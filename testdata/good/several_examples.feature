@@ -0,0 +1,14 @@
+Feature: Tagged Examples
+
+  Scenario Outline: minimalistic
+    Given the <what>
+
+    @foo
+    Examples:
+      | what |
+      | foo  |
+
+    @bar
+    Examples:
+      | what |
+      | bar  |